@@ -0,0 +1,296 @@
+use std::collections::HashSet;
+
+use num_bigint::BigInt;
+use num_traits::{One, Zero};
+use rust_ecc::math::prime_field_inv;
+
+use crate::r1cs::{fq, LinearCombination, R1CS};
+
+/// A soundness warning about a single R1CS variable
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Finding {
+    /// a private input or computed variable no constraint mentions
+    Unused(usize),
+    /// a computed variable the constraints do not pin down to one value given the inputs
+    Underdetermined(usize),
+    /// an output no chain of constraints connects to any input
+    OutputNotBound(usize),
+}
+
+impl Finding {
+    pub fn variable(&self) -> usize {
+        match self {
+            Finding::Unused(v) | Finding::Underdetermined(v) | Finding::OutputNotBound(v) => *v,
+        }
+    }
+
+    /// human readable warning, `names` as returned by `R1CS::variable_names`
+    pub fn message(&self, names: &[String]) -> String {
+        let name = &names[self.variable()];
+        match self {
+            Finding::Unused(_) => format!("`{}` appears in no constraint", name),
+            Finding::Underdetermined(_) => {
+                format!("`{}` is not uniquely determined by the inputs", name)
+            }
+            Finding::OutputNotBound(_) => format!("output `{}` does not depend on any input", name),
+        }
+    }
+}
+
+/// Look for under-constrained variables
+///
+/// Inputs and the constant one are known. A constraint determines a variable when it
+/// is the only unknown one left and it does not appear on both sides of the product,
+/// which assumes the known factor it is multiplied by is non-zero. A linear
+/// constraint over boolean unknowns with distinct power-of-two coefficients
+/// determines all of them, this covers bit decompositions. Everything computed that
+/// is still unknown once nothing changes is reported, as are outputs sharing no
+/// constraint path with an input.
+///
+/// The check is conservative in one direction only: a variable it reports may still
+/// be determined by reasoning it does not do, one it does not report can be trusted
+/// up to the non-zero assumption above.
+pub fn analyze(r1cs: &R1CS) -> Vec<Finding> {
+    let p = &r1cs.field_modulus;
+    let n = r1cs.num_variables;
+    let constraints: Vec<[&LinearCombination; 3]> = (0..r1cs.num_constraints())
+        .map(|i| [&r1cs.a[i], &r1cs.b[i], &r1cs.c[i]])
+        .collect();
+
+    let mut used = vec![false; n];
+    for lc in constraints.iter().flatten() {
+        for (v, _) in &lc.terms {
+            used[*v] = true;
+        }
+    }
+    let boolean: HashSet<usize> = constraints
+        .iter()
+        .filter_map(|[a, b, c]| boolean_variable(a, b, c, p))
+        .collect();
+
+    let inputs = r1cs.output_variables().end..r1cs.private_variables().end;
+    let mut known = vec![false; n];
+    known[R1CS::ONE] = true;
+    for v in inputs.clone() {
+        known[v] = true;
+    }
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for [a, b, c] in &constraints {
+            for v in determined(a, b, c, &known, &boolean, p) {
+                known[v] = true;
+                changed = true;
+            }
+        }
+    }
+
+    // variables connected to an input through shared constraints
+    let mut bound = vec![false; n];
+    for v in inputs.clone() {
+        bound[v] = true;
+    }
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for lcs in &constraints {
+            let vars = || {
+                lcs.iter()
+                    .flat_map(|lc| lc.terms.iter().map(|(v, _)| *v))
+                    .filter(|v| *v != R1CS::ONE)
+            };
+            if vars().any(|v| bound[v]) {
+                for v in vars() {
+                    changed |= !bound[v];
+                    bound[v] = true;
+                }
+            }
+        }
+    }
+
+    let mut findings = Vec::new();
+    for v in 1..n {
+        let public_input = r1cs.public_variables().contains(&v) && inputs.contains(&v);
+        if !used[v] && !public_input && !r1cs.output_variables().contains(&v) {
+            findings.push(Finding::Unused(v));
+            continue;
+        }
+        if !known[v] {
+            findings.push(Finding::Underdetermined(v));
+        }
+        if r1cs.output_variables().contains(&v) && !bound[v] {
+            findings.push(Finding::OutputNotBound(v));
+        }
+    }
+    findings
+}
+
+fn coefficient(lc: &LinearCombination, variable: usize) -> BigInt {
+    lc.terms
+        .iter()
+        .find(|(v, _)| *v == variable)
+        .map_or_else(BigInt::zero, |(_, c)| c.n.clone())
+}
+
+/// `k` if the linear combination is the constant `k * one`
+fn constant(lc: &LinearCombination) -> Option<BigInt> {
+    match lc.terms.as_slice() {
+        [] => Some(BigInt::zero()),
+        [(R1CS::ONE, k)] => Some(k.n.clone()),
+        _ => None,
+    }
+}
+
+/// the variable a constraint forces to be 0 or 1, i.e. `A·B - C` is a non-zero
+/// multiple of `v² - v` for the only variable `v` besides the constant one
+fn boolean_variable(
+    a: &LinearCombination,
+    b: &LinearCombination,
+    c: &LinearCombination,
+    p: &BigInt,
+) -> Option<usize> {
+    let vars: HashSet<usize> = [a, b, c]
+        .iter()
+        .flat_map(|lc| lc.terms.iter().map(|(v, _)| *v))
+        .filter(|v| *v != R1CS::ONE)
+        .collect();
+    let v = match vars.into_iter().collect::<Vec<_>>().as_slice() {
+        [v] => *v,
+        _ => return None,
+    };
+    let (a1, a0) = (coefficient(a, v), coefficient(a, R1CS::ONE));
+    let (b1, b0) = (coefficient(b, v), coefficient(b, R1CS::ONE));
+    let (c1, c0) = (coefficient(c, v), coefficient(c, R1CS::ONE));
+    let square = (&a1 * &b1) % p;
+    let linear = (&a1 * &b0 + &a0 * &b1 - c1 + &square) % p;
+    let constant = (a0 * b0 - c0) % p;
+    (!square.is_zero() && linear.is_zero() && constant.is_zero()).then_some(v)
+}
+
+/// unknown variables one constraint determines given the `known` ones
+fn determined(
+    a: &LinearCombination,
+    b: &LinearCombination,
+    c: &LinearCombination,
+    known: &[bool],
+    boolean: &HashSet<usize>,
+    p: &BigInt,
+) -> Vec<usize> {
+    let unknown = |lc: &LinearCombination| -> Vec<usize> {
+        lc.terms
+            .iter()
+            .map(|(v, _)| *v)
+            .filter(|v| !known[*v])
+            .collect()
+    };
+    // a constant factor makes the constraint linear, `k·A - C = 0` or `k·B - C = 0`
+    let linear = match (constant(a), constant(b)) {
+        (_, Some(k)) => Some(a.scale(&fq(k, p)).sub(c)),
+        (Some(k), _) => Some(b.scale(&fq(k, p)).sub(c)),
+        _ => None,
+    };
+    if let Some(linear) = linear {
+        let vars = unknown(&linear);
+        return match vars.as_slice() {
+            [_] => vars,
+            [] => Vec::new(),
+            _ if vars.iter().all(|v| boolean.contains(v)) && binary(&linear, &vars, p) => vars,
+            _ => Vec::new(),
+        };
+    }
+
+    let (in_a, in_b) = (unknown(a), unknown(b));
+    let mut vars: Vec<usize> = in_a
+        .iter()
+        .chain(&in_b)
+        .chain(&unknown(c))
+        .copied()
+        .collect();
+    vars.sort_unstable();
+    vars.dedup();
+    match vars.as_slice() {
+        [v] if !(in_a.contains(v) && in_b.contains(v)) => vars,
+        _ => Vec::new(),
+    }
+}
+
+/// the coefficients of `vars` are a common factor times distinct powers of two whose
+/// sum stays below the modulus, so every field value has at most one bit pattern
+fn binary(lc: &LinearCombination, vars: &[usize], p: &BigInt) -> bool {
+    let coefficients: Vec<BigInt> = vars.iter().map(|v| coefficient(lc, *v)).collect();
+    coefficients.iter().any(|reference| {
+        let inverse = prime_field_inv(reference.clone(), p.clone());
+        let mut exponents = HashSet::new();
+        let mut sum = BigInt::zero();
+        for coefficient in &coefficients {
+            let ratio = (coefficient * &inverse) % p;
+            if ratio.is_zero() || !(&ratio & (&ratio - BigInt::one())).is_zero() {
+                return false;
+            }
+            if !exponents.insert(ratio.bits()) {
+                return false;
+            }
+            sum += ratio;
+        }
+        sum < *p
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use super::{analyze, Finding};
+    use crate::constraint_system::{ConstraintSystem, R1CSBuilder};
+    use crate::gadgets::{assert_bool, to_bits};
+    use crate::{flatten, parse, R1CS};
+
+    fn messages(source: &str) -> Vec<String> {
+        let circuit = flatten(&parse(source).unwrap()).unwrap();
+        let r1cs = R1CS::from_circuit(&circuit, BigInt::from(1009)).unwrap();
+        let names = r1cs.variable_names(&circuit);
+        analyze(&r1cs).iter().map(|f| f.message(&names)).collect()
+    }
+
+    #[test]
+    fn square_roots_have_two_candidates() {
+        // the quotient is pinned down by `q * b = a`, either root satisfies `r * r = x`
+        assert_eq!(
+            messages("private x, a, b;\noutput q = a / b;\noutput r = sqrt(x);"),
+            ["`r` is not uniquely determined by the inputs"]
+        );
+        assert!(messages("private x, y;\nlet z = x * y + 3;\noutput o = z * z;").is_empty());
+    }
+
+    #[test]
+    fn unused_inputs_and_constant_outputs() {
+        assert_eq!(
+            messages("private a, b;\noutput c = a * 2;\noutput d = 5 * 7;"),
+            [
+                "output `d` does not depend on any input",
+                "`b` appears in no constraint"
+            ]
+        );
+    }
+
+    #[test]
+    fn bit_decompositions_are_determined() {
+        let p = BigInt::from(1009);
+        let mut builder = R1CSBuilder::new(p.clone()).unwrap();
+        let x = builder.alloc_input("x", None);
+        let x = builder.lc(x);
+        to_bits(&mut builder, &x, 4);
+        let r1cs = builder.into_r1cs();
+        assert_eq!(analyze(&r1cs), []);
+
+        // booleanity alone leaves two choices
+        let mut builder = R1CSBuilder::new(p).unwrap();
+        let x = builder.alloc_input("x", None);
+        let bit = builder.alloc("bit", None);
+        builder.enforce(builder.lc(x), builder.lc(x), builder.lc(x));
+        let bit = builder.lc(bit);
+        assert_bool(&mut builder, &bit);
+        let r1cs = builder.into_r1cs();
+        assert_eq!(analyze(&r1cs), [Finding::Underdetermined(2)]);
+    }
+}
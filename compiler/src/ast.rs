@@ -0,0 +1,184 @@
+use num_bigint::BigInt;
+
+/// Byte range of a node in the circuit source
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    /// smallest span covering both `self` and `other`
+    pub fn to(&self, other: Span) -> Span {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Visibility {
+    Public,
+    Private,
+}
+
+impl Visibility {
+    /// the keyword declaring an input with this visibility
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Private => "private",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    /// field division, computed as a hint and checked by a multiplication
+    Div,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExprKind {
+    Constant(BigInt),
+    Variable(Ident),
+    Neg(Box<Expr>),
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    /// `name(args...)`
+    Call {
+        name: Ident,
+        args: Vec<Expr>,
+    },
+    /// `[a, b, c]`
+    Array(Vec<Expr>),
+    /// `[value; count]`
+    Repeat {
+        value: Box<Expr>,
+        count: Box<Expr>,
+    },
+    /// `array[index]`, the index has to be known at compile time
+    Index {
+        array: Box<Expr>,
+        index: Box<Expr>,
+    },
+    /// `if cond then a else b`, `cond` has to be 0 or 1
+    If {
+        cond: Box<Expr>,
+        then_branch: Box<Expr>,
+        else_branch: Box<Expr>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StatementKind {
+    /// `public x;` or `private x;`, `private xs[4];` declares an array
+    Input {
+        visibility: Visibility,
+        name: Ident,
+        size: Option<usize>,
+    },
+    /// `let x = expr;` or `let mut x = expr;`
+    Let {
+        name: Ident,
+        mutable: bool,
+        value: Expr,
+    },
+    /// `x = expr;` or `xs[i] = expr;` for a mutable binding
+    Assign {
+        name: Ident,
+        index: Option<Expr>,
+        value: Expr,
+    },
+    /// `output x = expr;`, a public result of the circuit
+    Output { name: Ident, value: Expr },
+    /// `assert_eq(lhs, rhs);`
+    AssertEq { lhs: Expr, rhs: Expr },
+    /// `for i in start..end { ... }`, unrolled at compile time
+    For {
+        var: Ident,
+        start: Expr,
+        end: Expr,
+        body: Vec<Statement>,
+    },
+    /// `return expr;`, the last statement of a function
+    Return { value: Expr },
+    /// `table name = range(8);`, `xor(4)` or an array of constant entries, each entry
+    /// a constant or an array of up to three constants
+    Table { name: Ident, value: Expr },
+    /// `lookup(table, a, b);`, the values together have to be an entry of the table
+    Lookup { table: Ident, args: Vec<Expr> },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+/// `fn name(params) { body }`, inlined at every call
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Function {
+    pub name: Ident,
+    pub params: Vec<Ident>,
+    pub body: Vec<Statement>,
+    pub span: Span,
+}
+
+/// A parsed circuit, statements are kept in source order
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Program {
+    pub functions: Vec<Function>,
+    pub statements: Vec<Statement>,
+}
+
+impl Program {
+    /// names of all declared inputs with the given visibility, in declaration order
+    pub fn inputs(&self, visibility: Visibility) -> Vec<&Ident> {
+        self.statements
+            .iter()
+            .filter_map(|statement| match &statement.kind {
+                StatementKind::Input {
+                    visibility: v,
+                    name,
+                    ..
+                } if *v == visibility => Some(name),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// names of all declared outputs, in declaration order
+    pub fn outputs(&self) -> Vec<&Ident> {
+        self.statements
+            .iter()
+            .filter_map(|statement| match &statement.kind {
+                StatementKind::Output { name, .. } => Some(name),
+                _ => None,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,460 @@
+use std::collections::HashMap;
+
+use num_bigint::BigInt;
+use num_traits::{One, Zero};
+
+use crate::ast::{Span, Visibility};
+use crate::circuit::{Circuit, Gate, GateKind, Operand, WireInfo, WireKind};
+use crate::error::CompileError;
+
+// Front end for boolean circuits in the Bristol netlist formats
+//
+// A netlist starts with the gate and wire counts and the sizes of the inputs and
+// outputs, then one gate per line: input count, output count, input wires, output
+// wires and the operation, e.g. `2 1 0 1 2 XOR`. Bristol Fashion lists any number of
+// inputs and outputs, `2 64 64` then `1 64`, the older format has exactly two
+// inputs and one output on a single line, `64 64 64`. Inputs are the first wires,
+// outputs the last ones.
+//
+// Every gate becomes arithmetic gates that keep boolean values boolean:
+// AND is `ab`, XOR `a + b - 2ab`, OR `a + b - ab` and INV `1 - a`. Each input bit is
+// checked with `x * x == x`, which is all the circuit needs to reject other values.
+
+/// Wires a netlist may declare, far above the few hundred thousand of the largest
+/// published circuits
+pub const MAX_WIRES: usize = 1 << 24;
+
+/// Compile a Bristol or Bristol Fashion netlist
+///
+/// `visibility` gives one visibility per input, input `i` bit `j` is the wire named
+/// `in{i}_{j}` and output `i` bit `j` is `out{i}_{j}`, bits in wire order. Besides
+/// XOR, AND, INV and EQW this accepts NOT and OR, EQ for constant bits and MAND for
+/// several ANDs in one line. Errors point at the offending line.
+pub fn from_bristol(source: &str, visibility: &[Visibility]) -> Result<Circuit, CompileError> {
+    let lines = lines(source);
+    let mut lines = lines.iter();
+    let mut next = |what: &str| {
+        lines.next().ok_or_else(|| CompileError::Parse {
+            message: format!("expected {}, found the end of the netlist", what),
+            span: Span::new(source.len(), source.len()),
+        })
+    };
+    let (span, counts) = next("the gate and wire counts")?;
+    let [num_gates, num_wires] = numbers::<2>(counts, *span)?;
+    if num_wires > MAX_WIRES {
+        return Err(CompileError::Parse {
+            message: format!("{} wires exceed the limit of {}", num_wires, MAX_WIRES),
+            span: *span,
+        });
+    }
+    let (input_span, first) = next("the input sizes")?;
+    let second = next("a gate")?;
+    let (inputs, outputs, mut gate_lines) = if second.1.iter().all(|t| t.parse::<usize>().is_ok()) {
+        // Bristol Fashion, a count followed by the sizes
+        (
+            sizes(first, *input_span)?,
+            sizes(&second.1, second.0)?,
+            Vec::new(),
+        )
+    } else {
+        let [a, b, out] = numbers::<3>(first, *input_span)?;
+        (vec![a, b], vec![out], vec![second])
+    };
+    gate_lines.extend(lines);
+    if visibility.len() != inputs.len() {
+        return Err(CompileError::Parse {
+            message: format!(
+                "the netlist has {} inputs, {} visibilities given",
+                inputs.len(),
+                visibility.len()
+            ),
+            span: *input_span,
+        });
+    }
+    if gate_lines.len() != num_gates {
+        return Err(CompileError::Parse {
+            message: format!("expected {} gates, found {}", num_gates, gate_lines.len()),
+            span: *span,
+        });
+    }
+    let sum = |sizes: &[usize]| {
+        sizes
+            .iter()
+            .try_fold(0usize, |total, size| total.checked_add(*size))
+    };
+    let num_outputs = sum(&outputs).unwrap_or(usize::MAX);
+    let used = sum(&inputs).and_then(|num_inputs| num_inputs.checked_add(num_outputs));
+    if used.is_none_or(|used| used > num_wires) {
+        return Err(CompileError::Parse {
+            message: format!("the inputs and outputs need more than {} wires", num_wires),
+            span: *span,
+        });
+    }
+
+    let mut builder = Builder {
+        circuit: Circuit::default(),
+        values: HashMap::new(),
+    };
+    for (index, (size, visibility)) in inputs.iter().zip(visibility).enumerate() {
+        for bit in 0..*size {
+            let name = format!("in{}_{}", index, bit);
+            let wire = builder
+                .circuit
+                .new_wire(Some(name), WireKind::Input(*visibility));
+            builder
+                .values
+                .insert(builder.values.len(), Operand::Wire(wire));
+            let x = Operand::Wire(wire);
+            let squared = builder.gate(true, x.clone(), x.clone(), *input_span);
+            builder.circuit.gates.push(Gate {
+                kind: GateKind::AssertEq {
+                    lhs: squared,
+                    rhs: x,
+                },
+                span: *input_span,
+            });
+        }
+    }
+    for (span, tokens) in gate_lines {
+        builder.line(tokens, *span, num_wires)?;
+    }
+
+    let mut names = outputs
+        .iter()
+        .enumerate()
+        .flat_map(|(index, size)| (0..*size).map(move |bit| format!("out{}_{}", index, bit)));
+    for number in num_wires - num_outputs..num_wires {
+        let name = Some(names.next().unwrap());
+        let operand = builder
+            .values
+            .get(&number)
+            .cloned()
+            .ok_or(CompileError::Parse {
+                message: format!("output wire {} is never assigned", number),
+                span: *input_span,
+            })?;
+        match operand {
+            Operand::Wire(wire) if builder.circuit.wires[wire.0].kind == WireKind::Internal => {
+                builder.circuit.wires[wire.0] = WireInfo {
+                    name,
+                    kind: WireKind::Output,
+                };
+            }
+            // inputs, constants and bits already used for another output are copied
+            operand => {
+                let out = builder.circuit.new_wire(name, WireKind::Output);
+                let span = builder.circuit.gates.last().map_or(*input_span, |g| g.span);
+                builder.circuit.gates.push(Gate {
+                    kind: GateKind::Mul {
+                        lhs: operand,
+                        rhs: Operand::Constant(BigInt::one()),
+                        out,
+                    },
+                    span,
+                });
+            }
+        }
+    }
+    builder.circuit.check_outputs()?;
+    Ok(builder.circuit)
+}
+
+struct Builder {
+    circuit: Circuit,
+    /// value of every Bristol wire assigned so far
+    values: HashMap<usize, Operand>,
+}
+
+impl Builder {
+    fn line(&mut self, tokens: &[&str], span: Span, num_wires: usize) -> Result<(), CompileError> {
+        let error = |message: String| CompileError::Parse { message, span };
+        let (op, tokens) = tokens
+            .split_last()
+            .filter(|(_, rest)| rest.len() >= 2)
+            .ok_or_else(|| error("expected a gate".to_string()))?;
+        let [num_in, num_out] = numbers::<2>(&tokens[..2], span)?;
+        let wires = &tokens[2..];
+        let expected = num_in
+            .checked_add(num_out)
+            .filter(|expected| *expected <= num_wires)
+            .ok_or_else(|| {
+                error(format!(
+                    "a gate of the netlist has at most {} wires",
+                    num_wires
+                ))
+            })?;
+        if wires.len() != expected {
+            return Err(error(format!(
+                "expected {} wires, found {}",
+                expected,
+                wires.len()
+            )));
+        }
+        let (ins, outs) = wires.split_at(num_in);
+        let arity = |n: usize, m: usize| {
+            if (num_in, num_out) == (n, m) {
+                Ok(())
+            } else {
+                Err(error(format!(
+                    "`{}` takes {} input(s) and {} output(s)",
+                    op, n, m
+                )))
+            }
+        };
+
+        let results = match *op {
+            "EQ" => {
+                arity(1, 1)?;
+                match ins[0] {
+                    "0" | "1" => vec![Operand::Constant(ins[0].parse().unwrap())],
+                    bit => return Err(error(format!("expected a constant bit, found `{}`", bit))),
+                }
+            }
+            "MAND" if num_out.checked_mul(2) == Some(num_in) && num_out > 0 => {
+                let ins = self.read(ins, span, num_wires)?;
+                let (lhs, rhs) = ins.split_at(num_out);
+                lhs.iter()
+                    .zip(rhs)
+                    .map(|(a, b)| self.gate(true, a.clone(), b.clone(), span))
+                    .collect()
+            }
+            "MAND" => return Err(error("`MAND` takes 2n inputs and n outputs".to_string())),
+            "EQW" | "INV" | "NOT" => {
+                arity(1, 1)?;
+                let a = self.read(ins, span, num_wires)?.remove(0);
+                vec![match *op {
+                    "EQW" => a,
+                    _ => self.not(a, span),
+                }]
+            }
+            "AND" | "XOR" | "OR" => {
+                arity(2, 1)?;
+                let [a, b]: [Operand; 2] = self.read(ins, span, num_wires)?.try_into().unwrap();
+                vec![match *op {
+                    "AND" => self.gate(true, a, b, span),
+                    _ => {
+                        // a + b - k·ab with k = 2 for XOR and 1 for OR
+                        let k = if *op == "XOR" { -2 } else { -1 };
+                        let product = self.gate(true, a.clone(), b.clone(), span);
+                        let scaled = self.gate(true, product, Operand::Constant(k.into()), span);
+                        let sum = self.gate(false, a, b, span);
+                        self.gate(false, sum, scaled, span)
+                    }
+                }]
+            }
+            _ => return Err(error(format!("unknown gate `{}`", op))),
+        };
+        for (number, value) in outs.iter().zip(results) {
+            let number = self.wire(number, span, num_wires)?;
+            if self.values.insert(number, value).is_some() {
+                return Err(error(format!("wire {} is assigned twice", number)));
+            }
+        }
+        Ok(())
+    }
+
+    fn wire(&self, token: &str, span: Span, num_wires: usize) -> Result<usize, CompileError> {
+        match token.parse::<usize>() {
+            Ok(number) if number < num_wires => Ok(number),
+            _ => Err(CompileError::Parse {
+                message: format!("`{}` is not a wire of the netlist", token),
+                span,
+            }),
+        }
+    }
+
+    fn read(
+        &self,
+        tokens: &[&str],
+        span: Span,
+        num_wires: usize,
+    ) -> Result<Vec<Operand>, CompileError> {
+        tokens
+            .iter()
+            .map(|token| {
+                let number = self.wire(token, span, num_wires)?;
+                self.values
+                    .get(&number)
+                    .cloned()
+                    .ok_or_else(|| CompileError::Parse {
+                        message: format!("wire {} is read before it is assigned", number),
+                        span,
+                    })
+            })
+            .collect()
+    }
+
+    /// `1 - a`
+    fn not(&mut self, a: Operand, span: Span) -> Operand {
+        let negated = self.gate(true, a, Operand::Constant(BigInt::from(-1)), span);
+        self.gate(false, negated, Operand::Constant(BigInt::one()), span)
+    }
+
+    /// a multiplication or an addition on a fresh intermediate wire
+    fn gate(&mut self, mul: bool, lhs: Operand, rhs: Operand, span: Span) -> Operand {
+        let out = self.circuit.new_wire(None, WireKind::Internal);
+        let kind = if mul {
+            GateKind::Mul { lhs, rhs, out }
+        } else {
+            GateKind::Add { lhs, rhs, out }
+        };
+        self.circuit.gates.push(Gate { kind, span });
+        Operand::Wire(out)
+    }
+}
+
+/// non-empty lines split on whitespace, with the span of the line
+fn lines(source: &str) -> Vec<(Span, Vec<&str>)> {
+    let mut start = 0;
+    let mut lines = Vec::new();
+    for line in source.split_inclusive('\n') {
+        let text = line.trim_end();
+        let tokens: Vec<&str> = text.split_whitespace().collect();
+        if !tokens.is_empty() {
+            let indent = text.len() - text.trim_start().len();
+            lines.push((Span::new(start + indent, start + text.len()), tokens));
+        }
+        start += line.len();
+    }
+    lines
+}
+
+fn numbers<const N: usize>(tokens: &[&str], span: Span) -> Result<[usize; N], CompileError> {
+    let error = || CompileError::Parse {
+        message: format!("expected {} numbers", N),
+        span,
+    };
+    if tokens.len() != N {
+        return Err(error());
+    }
+    let mut numbers = [0; N];
+    for (number, token) in numbers.iter_mut().zip(tokens) {
+        *number = token.parse().map_err(|_| error())?;
+    }
+    Ok(numbers)
+}
+
+/// a Bristol Fashion size line, the count followed by that many sizes
+fn sizes(tokens: &[&str], span: Span) -> Result<Vec<usize>, CompileError> {
+    let numbers: Vec<usize> = tokens.iter().map(|t| t.parse().unwrap_or(0)).collect();
+    match numbers.split_first() {
+        Some((count, sizes)) if *count == sizes.len() && !numbers[1..].contains(&0) => {
+            Ok(sizes.to_vec())
+        }
+        _ => Err(CompileError::Parse {
+            message: "expected a count followed by that many sizes".to_string(),
+            span,
+        }),
+    }
+}
+
+/// the lowest `size` bits of `value`, least significant first, as circuit inputs
+///
+/// Any size works, bits above the value are zero, or one for a negative value in
+/// two's complement.
+pub fn bits(value: impl Into<BigInt>, size: usize) -> Vec<BigInt> {
+    let value = value.into();
+    (0..size as u64)
+        .map(|i| match value.bit(i) {
+            true => BigInt::one(),
+            false => BigInt::zero(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use super::{bits, from_bristol, MAX_WIRES};
+    use crate::ast::Visibility;
+    use crate::witness::generate_witness;
+    use crate::R1CS;
+
+    // 2-bit adder, outputs the sum bits and the carry
+    const ADDER: &str = "7 11
+2 2 2
+1 3
+
+2 1 0 2 4 AND
+2 1 1 3 5 XOR
+2 1 1 3 6 AND
+2 1 5 4 7 AND
+2 1 0 2 8 XOR
+2 1 5 4 9 XOR
+2 1 6 7 10 OR
+";
+
+    #[test]
+    fn adder_matches_integer_addition() {
+        let circuit = from_bristol(ADDER, &[Visibility::Private, Visibility::Public]).unwrap();
+        let outputs = circuit.outputs();
+        let names: Vec<String> = outputs.iter().map(|w| circuit.wire_name(*w)).collect();
+        assert_eq!(names, ["out0_0", "out0_1", "out0_2"]);
+        let r1cs = R1CS::from_circuit(&circuit, BigInt::from(1009)).unwrap();
+        for a in 0..4 {
+            for b in 0..4 {
+                let witness = generate_witness(&circuit, &r1cs, &bits(b, 2), &bits(a, 2)).unwrap();
+                assert!(r1cs.is_satisfied(&witness));
+                let sum: Vec<BigInt> = outputs
+                    .iter()
+                    .map(|w| witness[r1cs.wire_variables[w.0].unwrap()].n.clone())
+                    .collect();
+                assert_eq!(sum, bits(a + b, 3));
+            }
+        }
+        // a 2 passes every gate but the bit check
+        let witness = generate_witness(&circuit, &r1cs, &[2.into(), 0.into()], &bits(1, 2));
+        assert!(!witness.is_ok_and(|w| r1cs.is_satisfied(&w)));
+
+        // inputs wider than a machine word
+        let wide = bits(BigInt::from(5) << 100u32, 128);
+        assert_eq!(wide.len(), 128);
+        assert_eq!(wide.iter().filter(|b| **b == BigInt::from(1)).count(), 2);
+        assert_eq!((wide[100].clone(), wide[102].clone()), (1.into(), 1.into()));
+        assert_eq!(bits(-1, 80), vec![BigInt::from(1); 80]);
+    }
+
+    #[test]
+    fn formats_and_errors() {
+        let old = "2 4\n1 1 1\n1 1 0 2 INV\n2 1 2 1 3 OR\n";
+        let circuit = from_bristol(old, &[Visibility::Private; 2]).unwrap();
+        assert_eq!(
+            circuit.to_string(),
+            "w1 = in0_0 * in0_0\nassert w1 == in0_0\nw3 = in1_0 * in1_0\nassert w3 == in1_0\n\
+             w4 = in0_0 * -1\nw5 = w4 + 1\n\
+             w6 = w5 * in1_0\nw7 = w6 * -1\nw8 = w5 + in1_0\nout0_0 = w8 + w7\n"
+        );
+
+        let err =
+            from_bristol("1 3\n1 1\n1 1\n2 1 0 1 2 NAND\n", &[Visibility::Private]).unwrap_err();
+        assert_eq!(err.to_string(), "unknown gate `NAND`");
+        assert_eq!(err.span().unwrap().start, 12);
+        let err =
+            from_bristol("1 3\n1 1\n1 1\n2 1 0 1 2 AND\n", &[Visibility::Private]).unwrap_err();
+        assert_eq!(err.to_string(), "wire 1 is read before it is assigned");
+
+        // counts that overflow or would allocate without bound
+        let max = usize::MAX;
+        for (source, visibility, message) in [
+            (
+                format!("1 3\n1 1\n1 1\n2 {} 0 1 2 AND\n", max),
+                1,
+                "a gate of the netlist has at most 3 wires".to_string(),
+            ),
+            (
+                format!("1 3\n2 {} {}\n1 1\n2 1 0 1 2 AND\n", max, max),
+                2,
+                "the inputs and outputs need more than 3 wires".to_string(),
+            ),
+            (
+                "1 1000000000000\n1 1000000000000\n1 1\n2 1 0 1 2 AND\n".to_string(),
+                1,
+                format!("1000000000000 wires exceed the limit of {}", MAX_WIRES),
+            ),
+        ] {
+            let err = from_bristol(&source, &vec![Visibility::Private; visibility]).unwrap_err();
+            assert_eq!(err.to_string(), message);
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use std::fmt;
+
+use num_bigint::{BigInt, Sign};
+use num_traits::One;
+use rust_ecc::field::FQ;
+
+use crate::r1cs::{fq, LinearCombination, R1CS};
+
+// Readers and writers for the iden3 binary formats circom and snarkjs use
+//
+// Both files are a magic string, a version and a list of sections, each section a
+// u32 type, a u64 byte length and its content. Integers are little endian and field
+// elements are written in `n8` bytes, the modulus size rounded up to whole u64 words.
+// The variable layout `[1, outputs, public inputs, private inputs, rest]` is the same
+// as ours, so variables map one to one onto circom wires.
+
+const R1CS_HEADER: u32 = 1;
+const R1CS_CONSTRAINTS: u32 = 2;
+const R1CS_WIRE_LABELS: u32 = 3;
+const WTNS_HEADER: u32 = 1;
+const WTNS_VALUES: u32 = 2;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FormatError {
+    /// the file does not start with `r1cs` or `wtns`
+    Magic {
+        expected: &'static str,
+    },
+    UnsupportedVersion(u32),
+    /// the data ends in the middle of a value
+    Truncated,
+    MissingSection(u32),
+    /// the header and the content disagree
+    Malformed(&'static str),
+    /// a count or size does not fit in the u32 the format stores it in
+    TooLarge(&'static str),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatError::Magic { expected } => write!(f, "not a `.{}` file", expected),
+            FormatError::UnsupportedVersion(v) => write!(f, "unsupported version {}", v),
+            FormatError::Truncated => write!(f, "unexpected end of file"),
+            FormatError::MissingSection(s) => write!(f, "missing section {}", s),
+            FormatError::Malformed(message) => write!(f, "{}", message),
+            FormatError::TooLarge(what) => write!(f, "too many {} for a u32", what),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+/// Encode a constraint system as a circom `.r1cs` file, version 1
+///
+/// Every variable is labelled with its own index. Fails on a count the u32 fields of
+/// the format cannot hold.
+pub fn write_r1cs(r1cs: &R1CS) -> Result<Vec<u8>, FormatError> {
+    let n8 = field_size(&r1cs.field_modulus);
+    let mut header = Vec::new();
+    put_u32(&mut header, to_u32(n8, "bytes per field element")?);
+    put_field(&mut header, &r1cs.field_modulus, n8);
+    for (count, what) in [
+        (r1cs.num_variables, "wires"),
+        (r1cs.num_outputs, "outputs"),
+        (r1cs.num_public, "public inputs"),
+        (r1cs.num_private, "private inputs"),
+    ] {
+        put_u32(&mut header, to_u32(count, what)?);
+    }
+    put_u64(&mut header, r1cs.num_variables as u64);
+    put_u32(&mut header, to_u32(r1cs.num_constraints(), "constraints")?);
+
+    let mut constraints = Vec::new();
+    for i in 0..r1cs.num_constraints() {
+        for lc in [&r1cs.a[i], &r1cs.b[i], &r1cs.c[i]] {
+            put_u32(&mut constraints, to_u32(lc.terms.len(), "terms")?);
+            for (v, coeff) in &lc.terms {
+                put_u32(&mut constraints, to_u32(*v, "wires")?);
+                put_field(&mut constraints, &coeff.n, n8);
+            }
+        }
+    }
+
+    let mut labels = Vec::new();
+    for v in 0..r1cs.num_variables {
+        put_u64(&mut labels, v as u64);
+    }
+    file(
+        b"r1cs",
+        1,
+        &[
+            (R1CS_HEADER, header),
+            (R1CS_CONSTRAINTS, constraints),
+            (R1CS_WIRE_LABELS, labels),
+        ],
+    )
+}
+
+/// Decode a circom `.r1cs` file, wire labels are ignored
+pub fn read_r1cs(data: &[u8]) -> Result<R1CS, FormatError> {
+    let sections = sections(data, b"r1cs", "r1cs", &[1])?;
+    let mut header = Reader::new(section(&sections, R1CS_HEADER)?);
+    let n8 = header.u32()? as usize;
+    let field_modulus = header.modulus(n8)?;
+    let num_variables = header.u32()? as usize;
+    let num_outputs = header.u32()? as usize;
+    let num_public = header.u32()? as usize;
+    let num_private = header.u32()? as usize;
+    let _num_labels = header.u64()?;
+    let num_constraints = header.u32()? as usize;
+    if 1 + num_outputs + num_public + num_private > num_variables {
+        return Err(FormatError::Malformed("more inputs and outputs than wires"));
+    }
+
+    let mut r1cs = R1CS {
+        field_modulus,
+        num_outputs,
+        num_public,
+        num_private,
+        num_variables,
+        a: Vec::new(),
+        b: Vec::new(),
+        c: Vec::new(),
+        wire_variables: Vec::new(),
+        constraint_gates: Vec::new(),
+    };
+    let mut constraints = Reader::new(section(&sections, R1CS_CONSTRAINTS)?);
+    for _ in 0..num_constraints {
+        let mut lcs = Vec::new();
+        for _ in 0..3 {
+            let mut lc = LinearCombination::default();
+            for _ in 0..constraints.u32()? {
+                let v = constraints.u32()? as usize;
+                if v >= num_variables {
+                    return Err(FormatError::Malformed(
+                        "constraint refers to a missing wire",
+                    ));
+                }
+                lc.add_term(v, fq(constraints.field(n8)?, &r1cs.field_modulus));
+            }
+            lcs.push(lc);
+        }
+        let c = lcs.pop().unwrap();
+        let b = lcs.pop().unwrap();
+        let a = lcs.pop().unwrap();
+        r1cs.push(a, b, c);
+    }
+    Ok(r1cs)
+}
+
+/// Encode a witness as a circom `.wtns` file, version 2
+pub fn write_wtns(witness: &[FQ], field_modulus: &BigInt) -> Result<Vec<u8>, FormatError> {
+    let n8 = field_size(field_modulus);
+    let mut header = Vec::new();
+    put_u32(&mut header, to_u32(n8, "bytes per field element")?);
+    put_field(&mut header, field_modulus, n8);
+    put_u32(&mut header, to_u32(witness.len(), "witness values")?);
+    let mut values = Vec::new();
+    for value in witness {
+        put_field(&mut values, &value.n, n8);
+    }
+    file(b"wtns", 2, &[(WTNS_HEADER, header), (WTNS_VALUES, values)])
+}
+
+/// Decode a circom `.wtns` file, the values are reduced into the field of its header
+pub fn read_wtns(data: &[u8]) -> Result<Vec<FQ>, FormatError> {
+    let sections = sections(data, b"wtns", "wtns", &[2])?;
+    let mut header = Reader::new(section(&sections, WTNS_HEADER)?);
+    let n8 = header.u32()? as usize;
+    let field_modulus = header.modulus(n8)?;
+    let count = header.u32()? as usize;
+    let mut values = Reader::new(section(&sections, WTNS_VALUES)?);
+    (0..count)
+        .map(|_| Ok(fq(values.field(n8)?, &field_modulus)))
+        .collect()
+}
+
+/// bytes per field element, whole 64 bit words as circom uses
+fn field_size(field_modulus: &BigInt) -> usize {
+    (field_modulus.bits() as usize).div_ceil(64).max(1) * 8
+}
+
+fn to_u32(n: usize, what: &'static str) -> Result<u32, FormatError> {
+    u32::try_from(n).map_err(|_| FormatError::TooLarge(what))
+}
+
+fn put_u32(out: &mut Vec<u8>, n: u32) {
+    out.extend_from_slice(&n.to_le_bytes());
+}
+
+fn put_u64(out: &mut Vec<u8>, n: u64) {
+    out.extend_from_slice(&n.to_le_bytes());
+}
+
+fn put_field(out: &mut Vec<u8>, n: &BigInt, n8: usize) {
+    let (_, mut bytes) = n.to_bytes_le();
+    bytes.resize(n8, 0);
+    out.extend_from_slice(&bytes);
+}
+
+fn file(
+    magic: &[u8; 4],
+    version: u32,
+    sections: &[(u32, Vec<u8>)],
+) -> Result<Vec<u8>, FormatError> {
+    let mut out = magic.to_vec();
+    put_u32(&mut out, version);
+    put_u32(&mut out, to_u32(sections.len(), "sections")?);
+    for (kind, content) in sections {
+        put_u32(&mut out, *kind);
+        put_u64(&mut out, content.len() as u64);
+        out.extend_from_slice(content);
+    }
+    Ok(out)
+}
+
+/// the sections of a file, in file order
+fn sections<'a>(
+    data: &'a [u8],
+    magic: &[u8; 4],
+    name: &'static str,
+    versions: &[u32],
+) -> Result<Vec<(u32, &'a [u8])>, FormatError> {
+    let mut reader = Reader::new(data);
+    if reader.bytes(4).ok() != Some(&magic[..]) {
+        return Err(FormatError::Magic { expected: name });
+    }
+    let version = reader.u32()?;
+    if !versions.contains(&version) {
+        return Err(FormatError::UnsupportedVersion(version));
+    }
+    (0..reader.u32()?)
+        .map(|_| {
+            let kind = reader.u32()?;
+            let len = reader.u64()? as usize;
+            Ok((kind, reader.bytes(len)?))
+        })
+        .collect()
+}
+
+fn section<'a>(sections: &[(u32, &'a [u8])], kind: u32) -> Result<&'a [u8], FormatError> {
+    sections
+        .iter()
+        .find(|(k, _)| *k == kind)
+        .map(|(_, content)| *content)
+        .ok_or(FormatError::MissingSection(kind))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data, pos: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], FormatError> {
+        let end = self.pos.checked_add(len).ok_or(FormatError::Truncated)?;
+        let bytes = self.data.get(self.pos..end).ok_or(FormatError::Truncated)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, FormatError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, FormatError> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    fn field(&mut self, n8: usize) -> Result<BigInt, FormatError> {
+        if n8 == 0 {
+            return Err(FormatError::Malformed("field elements of size zero"));
+        }
+        Ok(BigInt::from_bytes_le(Sign::Plus, self.bytes(n8)?))
+    }
+
+    /// the prime of a header, values are reduced by it so it has to be at least two
+    fn modulus(&mut self, n8: usize) -> Result<BigInt, FormatError> {
+        let modulus = self.field(n8)?;
+        if modulus <= BigInt::one() {
+            return Err(FormatError::Malformed("the prime must be greater than one"));
+        }
+        Ok(modulus)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use super::{read_r1cs, read_wtns, to_u32, write_r1cs, write_wtns, FormatError};
+    use crate::{flatten, generate_witness, parse, Field, R1CS};
+
+    // `c <== a * b` compiled by circom over the bn128 scalar field
+    const MULTIPLIER_R1CS: &[u8] = include_bytes!("../fixtures/multiplier.r1cs");
+    // a = 2, b = 3
+    const MULTIPLIER_WTNS: &[u8] = include_bytes!("../fixtures/multiplier.wtns");
+
+    #[test]
+    fn reads_circom_fixtures() {
+        let r1cs = read_r1cs(MULTIPLIER_R1CS).unwrap();
+        assert_eq!(r1cs.field_modulus, Field::Bn128.modulus());
+        assert_eq!(
+            (r1cs.num_variables, r1cs.num_outputs, r1cs.num_private),
+            (4, 1, 2)
+        );
+        assert_eq!(r1cs.num_constraints(), 1);
+        let witness = read_wtns(MULTIPLIER_WTNS).unwrap();
+        assert_eq!(witness[1].n, BigInt::from(6));
+        assert!(r1cs.is_satisfied(&witness));
+
+        // writing them back gives the same bytes
+        assert_eq!(write_r1cs(&r1cs).unwrap(), MULTIPLIER_R1CS);
+        assert_eq!(
+            write_wtns(&witness, &Field::Bn128.modulus()).unwrap(),
+            MULTIPLIER_WTNS
+        );
+    }
+
+    #[test]
+    fn round_trips_compiled_circuits() {
+        let source = "public y;\nprivate x;\nlet z = x * x * x;\nassert_eq(z + x + 5, y);\noutput o = z - 1;";
+        let circuit = flatten(&parse(source).unwrap()).unwrap();
+        let p = BigInt::from(1009);
+        let r1cs = R1CS::from_circuit_merged(&circuit, p.clone()).unwrap();
+        let witness = generate_witness(&circuit, &r1cs, &[35.into()], &[3.into()]).unwrap();
+
+        let read = read_r1cs(&write_r1cs(&r1cs).unwrap()).unwrap();
+        assert_eq!((&read.a, &read.b, &read.c), (&r1cs.a, &r1cs.b, &r1cs.c));
+        assert_eq!(read.public_variables(), r1cs.public_variables());
+        assert_eq!(read.private_variables(), r1cs.private_variables());
+        assert_eq!(
+            read_wtns(&write_wtns(&witness, &p).unwrap()).unwrap(),
+            witness
+        );
+
+        assert_eq!(
+            read_r1cs(MULTIPLIER_WTNS),
+            Err(FormatError::Magic { expected: "r1cs" })
+        );
+        assert_eq!(
+            read_r1cs(&MULTIPLIER_R1CS[..100]),
+            Err(FormatError::Truncated)
+        );
+
+        // the prime follows the magic, version, section count, section header and n8
+        let malformed = FormatError::Malformed("the prime must be greater than one");
+        let mut data = write_r1cs(&r1cs).unwrap();
+        data[28..36].fill(0);
+        assert_eq!(read_r1cs(&data).unwrap_err(), malformed);
+        let mut data = write_wtns(&witness, &p).unwrap();
+        data[28..36].copy_from_slice(&1u64.to_le_bytes());
+        assert_eq!(read_wtns(&data).unwrap_err(), malformed);
+
+        // counts past u32::MAX are rejected instead of wrapping
+        assert_eq!(
+            to_u32(1 << 32, "wires"),
+            Err(FormatError::TooLarge("wires"))
+        );
+        assert_eq!(
+            FormatError::TooLarge("wires").to_string(),
+            "too many wires for a u32"
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_traits::{Signed, Zero};
+use rust_ecc::field::{FqElement, FQ};
+use rust_ecc::math::{prime_field_inv, prime_field_sqrt};
+
+use crate::ast::{Span, Visibility};
+use crate::error::CompileError;
+use crate::plonk::LookupTable;
+use crate::r1cs::fq;
+
+/// Index of a wire in `Circuit::wires`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Wire(pub usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WireKind {
+    Input(Visibility),
+    /// public result declared with `output`
+    Output,
+    /// output of a gate
+    Internal,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WireInfo {
+    /// source name for inputs and `let` bindings, fresh intermediates have none
+    pub name: Option<String>,
+    pub kind: WireKind,
+}
+
+/// A gate input, either a wire or a constant that has not been reduced into a field yet
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Operand {
+    Wire(Wire),
+    Constant(BigInt),
+}
+
+impl Operand {
+    pub fn wire(&self) -> Option<Wire> {
+        match self {
+            Operand::Wire(wire) => Some(*wire),
+            Operand::Constant(_) => None,
+        }
+    }
+}
+
+/// Value the prover computes outside the circuit, it has to be checked by other gates
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Hint {
+    /// `a / b`, checked as `out * b == a`
+    Div,
+    /// `x^-1`, checked as `x * out == 1`
+    Inverse,
+    /// a square root of `x`, checked as `out * out == x`, which both roots satisfy
+    ///
+    /// The witness generator picks the smaller one, nothing in the circuit does.
+    Sqrt,
+}
+
+impl Hint {
+    /// the hint called by a builtin function name
+    pub fn builtin(name: &str) -> Option<Hint> {
+        match name {
+            "inv" => Some(Hint::Inverse),
+            "sqrt" => Some(Hint::Sqrt),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Hint::Div => "div",
+            Hint::Inverse => "inv",
+            Hint::Sqrt => "sqrt",
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        match self {
+            Hint::Div => 2,
+            Hint::Inverse | Hint::Sqrt => 1,
+        }
+    }
+
+    /// `None` for a zero divisor or a non-residue, there is no value satisfying the check
+    pub fn compute(&self, args: &[FQ], field_modulus: &BigInt) -> Option<FQ> {
+        let inverse = |x: &FQ| {
+            (!x.n.is_zero()).then(|| {
+                fq(
+                    prime_field_inv(x.n.clone(), field_modulus.clone()),
+                    field_modulus,
+                )
+            })
+        };
+        match self {
+            Hint::Div => Some(args[0].mul(FqElement::FQ(inverse(&args[1])?))),
+            Hint::Inverse => inverse(&args[0]),
+            Hint::Sqrt => prime_field_sqrt(args[0].n.clone(), field_modulus.clone())
+                .map(|root| fq(root, field_modulus)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GateKind {
+    /// out = lhs + rhs
+    Add {
+        lhs: Operand,
+        rhs: Operand,
+        out: Wire,
+    },
+    /// out = lhs * rhs
+    Mul {
+        lhs: Operand,
+        rhs: Operand,
+        out: Wire,
+    },
+    /// lhs == rhs
+    AssertEq { lhs: Operand, rhs: Operand },
+    /// out = hint(args), assigned by the prover without adding a constraint
+    Hint {
+        hint: Hint,
+        args: Vec<Operand>,
+        out: Wire,
+    },
+    /// the values of `args` form an entry of `Circuit::tables[table]`
+    Lookup { table: usize, args: Vec<Wire> },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Gate {
+    pub kind: GateKind,
+    /// source expression or statement the gate was generated from
+    pub span: Span,
+}
+
+/// Three-address gate list produced by `flatten`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Circuit {
+    pub wires: Vec<WireInfo>,
+    pub gates: Vec<Gate>,
+    /// tables lookup gates refer to by index
+    pub tables: Vec<LookupTable>,
+}
+
+impl Circuit {
+    pub fn new_wire(&mut self, name: Option<String>, kind: WireKind) -> Wire {
+        self.wires.push(WireInfo { name, kind });
+        Wire(self.wires.len() - 1)
+    }
+
+    /// input wires with the given visibility, in declaration order
+    pub fn inputs(&self, visibility: Visibility) -> Vec<Wire> {
+        self.wires
+            .iter()
+            .enumerate()
+            .filter(|(_, info)| info.kind == WireKind::Input(visibility))
+            .map(|(index, _)| Wire(index))
+            .collect()
+    }
+
+    /// output wires, in wire order
+    pub fn outputs(&self) -> Vec<Wire> {
+        self.wires
+            .iter()
+            .enumerate()
+            .filter(|(_, info)| info.kind == WireKind::Output)
+            .map(|(index, _)| Wire(index))
+            .collect()
+    }
+
+    /// source name of a wire, or `w<index>` for unnamed intermediates
+    pub fn wire_name(&self, wire: Wire) -> String {
+        match &self.wires[wire.0].name {
+            Some(name) => name.clone(),
+            None => format!("w{}", wire.0),
+        }
+    }
+
+    /// every output has to be computed or checked by an arithmetic gate, a wire only a
+    /// hint writes could be assigned anything by the prover
+    pub fn check_outputs(&self) -> Result<(), CompileError> {
+        let mut constrained = vec![false; self.wires.len()];
+        for gate in &self.gates {
+            let wires: Vec<Wire> = match &gate.kind {
+                GateKind::Add { lhs, rhs, out } | GateKind::Mul { lhs, rhs, out } => {
+                    vec![Some(*out), lhs.wire(), rhs.wire()]
+                        .into_iter()
+                        .flatten()
+                        .collect()
+                }
+                GateKind::AssertEq { lhs, rhs } => {
+                    [lhs.wire(), rhs.wire()].into_iter().flatten().collect()
+                }
+                GateKind::Hint { .. } => Vec::new(),
+                GateKind::Lookup { args, .. } => args.clone(),
+            };
+            for wire in wires {
+                constrained[wire.0] = true;
+            }
+        }
+        match self.outputs().into_iter().find(|wire| !constrained[wire.0]) {
+            Some(wire) => Err(CompileError::UnconstrainedOutput {
+                name: self.wire_name(wire),
+                span: self
+                    .gates
+                    .iter()
+                    .find(|gate| matches!(gate.kind, GateKind::Hint { out, .. } if out == wire))
+                    .map(|gate| gate.span),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// every constant operand with the span of the gate using it, table entries come
+    /// with the first lookup into the table
+    pub fn constants(&self) -> Vec<(Span, &BigInt)> {
+        let mut constants = Vec::new();
+        let mut seen = vec![false; self.tables.len()];
+        for gate in &self.gates {
+            let operands: Vec<&Operand> = match &gate.kind {
+                GateKind::Add { lhs, rhs, .. }
+                | GateKind::Mul { lhs, rhs, .. }
+                | GateKind::AssertEq { lhs, rhs } => vec![lhs, rhs],
+                GateKind::Hint { args, .. } => args.iter().collect(),
+                GateKind::Lookup { table, .. } => {
+                    if !std::mem::replace(&mut seen[*table], true) {
+                        let entries = self.tables[*table].entries.iter().flatten();
+                        constants.extend(entries.map(|n| (gate.span, n)));
+                    }
+                    Vec::new()
+                }
+            };
+            for operand in operands {
+                if let Operand::Constant(n) = operand {
+                    constants.push((gate.span, n));
+                }
+            }
+        }
+        constants
+    }
+
+    /// every constant has to be smaller than the modulus in absolute value, reducing a
+    /// larger one gives a circuit that means something else in every field
+    pub fn check_constants(&self, field_modulus: &BigInt) -> Result<(), CompileError> {
+        match self
+            .constants()
+            .into_iter()
+            .find(|(_, n)| n.abs() >= *field_modulus)
+        {
+            Some((span, n)) => Err(CompileError::ConstantOutOfRange {
+                value: n.clone(),
+                field_modulus: field_modulus.clone(),
+                span,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn operand_name(&self, operand: &Operand) -> String {
+        match operand {
+            Operand::Wire(wire) => self.wire_name(*wire),
+            Operand::Constant(n) => n.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Circuit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for gate in &self.gates {
+            match &gate.kind {
+                GateKind::Add { lhs, rhs, out } => writeln!(
+                    f,
+                    "{} = {} + {}",
+                    self.wire_name(*out),
+                    self.operand_name(lhs),
+                    self.operand_name(rhs)
+                )?,
+                GateKind::Mul { lhs, rhs, out } => writeln!(
+                    f,
+                    "{} = {} * {}",
+                    self.wire_name(*out),
+                    self.operand_name(lhs),
+                    self.operand_name(rhs)
+                )?,
+                GateKind::AssertEq { lhs, rhs } => writeln!(
+                    f,
+                    "assert {} == {}",
+                    self.operand_name(lhs),
+                    self.operand_name(rhs)
+                )?,
+                GateKind::Hint { hint, args, out } => {
+                    let args: Vec<String> = args.iter().map(|a| self.operand_name(a)).collect();
+                    writeln!(
+                        f,
+                        "{} <- {}({})",
+                        self.wire_name(*out),
+                        hint.name(),
+                        args.join(", ")
+                    )?
+                }
+                GateKind::Lookup { table, args } => {
+                    let args: Vec<String> = args.iter().map(|w| self.wire_name(*w)).collect();
+                    writeln!(
+                        f,
+                        "lookup {}({})",
+                        self.tables[*table].name,
+                        args.join(", ")
+                    )?
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,357 @@
+use num_bigint::BigInt;
+use num_traits::{One, Zero};
+use rust_ecc::field::{FqElement, FQ};
+
+use crate::error::CompileError;
+use crate::r1cs::{check_modulus, fq, LinearCombination, R1CS};
+
+/// Handle to an allocated variable, `Variable(0)` is the constant one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Variable(pub usize);
+
+/// Interface for writing circuits as Rust code
+///
+/// A gadget written against this trait produces the constraint system when run on an
+/// `R1CSBuilder` and the matching witness when run on a `WitnessBuilder`. Linear
+/// combinations refer to variables by `Variable::0`.
+pub trait ConstraintSystem {
+    fn field_modulus(&self) -> &BigInt;
+
+    /// allocate an intermediate variable the circuit computes, `value` is ignored when
+    /// only constraints are built
+    fn alloc(&mut self, name: &str, value: Option<FQ>) -> Variable;
+
+    /// allocate a private input
+    fn alloc_input(&mut self, name: &str, value: Option<FQ>) -> Variable;
+
+    /// move a variable into the public part of the variable vector
+    fn mark_public(&mut self, variable: Variable);
+
+    /// add the constraint `a * b = c`
+    fn enforce(&mut self, a: LinearCombination, b: LinearCombination, c: LinearCombination);
+
+    /// assigned value of a variable, `None` when only constraints are built
+    fn value(&self, variable: Variable) -> Option<FQ>;
+
+    fn one(&self) -> Variable {
+        Variable(R1CS::ONE)
+    }
+
+    /// allocate a public input
+    fn alloc_public(&mut self, name: &str, value: Option<FQ>) -> Variable {
+        let variable = self.alloc_input(name, value);
+        self.mark_public(variable);
+        variable
+    }
+
+    /// `1 * variable`
+    fn lc(&self, variable: Variable) -> LinearCombination {
+        let mut lc = LinearCombination::default();
+        lc.add_term(variable.0, fq(BigInt::one(), self.field_modulus()));
+        lc
+    }
+
+    /// `k * one`
+    fn constant(&self, k: BigInt) -> LinearCombination {
+        let mut lc = LinearCombination::default();
+        lc.add_term(R1CS::ONE, fq(k, self.field_modulus()));
+        lc
+    }
+
+    /// value of a linear combination, `None` if any of its variables is unassigned
+    fn evaluate(&self, lc: &LinearCombination) -> Option<FQ> {
+        lc.terms.iter().try_fold(
+            fq(BigInt::zero(), self.field_modulus()),
+            |acc, (v, coeff)| {
+                let value = self.value(Variable(*v))?;
+                Some(acc.add(FqElement::FQ(coeff.mul(FqElement::FQ(value)))))
+            },
+        )
+    }
+}
+
+/// Where an allocated variable goes in the final variable vector, in layout order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Role {
+    Public,
+    Input,
+    Intermediate,
+}
+
+/// Names and roles of allocated variables, shared by both builders so they agree on
+/// the final variable layout
+#[derive(Clone, Debug)]
+struct Allocation {
+    names: Vec<String>,
+    roles: Vec<Role>,
+}
+
+impl Allocation {
+    fn new() -> Allocation {
+        Allocation {
+            names: vec!["one".to_string()],
+            roles: vec![Role::Public],
+        }
+    }
+
+    fn alloc(&mut self, name: &str, role: Role) -> Variable {
+        self.names.push(name.to_string());
+        self.roles.push(role);
+        Variable(self.names.len() - 1)
+    }
+
+    /// new index of every allocated variable: the constant one, public variables,
+    /// private inputs, then intermediates, each group in allocation order
+    fn layout(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (1..self.names.len()).collect();
+        order.sort_by_key(|v| self.roles[*v]);
+        let mut layout = vec![0; self.names.len()];
+        for (index, v) in order.into_iter().enumerate() {
+            layout[v] = index + 1;
+        }
+        layout
+    }
+
+    fn count(&self, role: Role) -> usize {
+        self.roles[1..].iter().filter(|r| **r == role).count()
+    }
+}
+
+/// Collects constraints into an `R1CS`
+pub struct R1CSBuilder {
+    field_modulus: BigInt,
+    allocation: Allocation,
+    constraints: Vec<(LinearCombination, LinearCombination, LinearCombination)>,
+}
+
+impl R1CSBuilder {
+    /// fails on a modulus below two
+    pub fn new(field_modulus: impl Into<BigInt>) -> Result<R1CSBuilder, CompileError> {
+        let field_modulus = field_modulus.into();
+        check_modulus(&field_modulus)?;
+        Ok(R1CSBuilder {
+            field_modulus,
+            allocation: Allocation::new(),
+            constraints: Vec::new(),
+        })
+    }
+
+    /// variable names in the layout of the finished `R1CS`
+    pub fn variable_names(&self) -> Vec<String> {
+        let mut names = self.allocation.names.clone();
+        for (v, index) in self.allocation.layout().into_iter().enumerate() {
+            names[index] = self.allocation.names[v].clone();
+        }
+        names
+    }
+
+    /// public variables count as public inputs, allocated intermediates are neither
+    /// inputs nor outputs
+    pub fn into_r1cs(self) -> R1CS {
+        let layout = self.allocation.layout();
+        let mut r1cs = R1CS {
+            field_modulus: self.field_modulus,
+            num_outputs: 0,
+            num_public: self.allocation.count(Role::Public),
+            num_private: self.allocation.count(Role::Input),
+            num_variables: layout.len(),
+            a: Vec::new(),
+            b: Vec::new(),
+            c: Vec::new(),
+            wire_variables: Vec::new(),
+            constraint_gates: Vec::new(),
+        };
+        let relabel = |lc: LinearCombination| LinearCombination {
+            terms: lc
+                .terms
+                .into_iter()
+                .map(|(v, coeff)| (layout[v], coeff))
+                .collect(),
+        };
+        for (a, b, c) in self.constraints {
+            r1cs.push(relabel(a), relabel(b), relabel(c));
+        }
+        r1cs
+    }
+}
+
+impl ConstraintSystem for R1CSBuilder {
+    fn field_modulus(&self) -> &BigInt {
+        &self.field_modulus
+    }
+
+    fn alloc(&mut self, name: &str, _value: Option<FQ>) -> Variable {
+        self.allocation.alloc(name, Role::Intermediate)
+    }
+
+    fn alloc_input(&mut self, name: &str, _value: Option<FQ>) -> Variable {
+        self.allocation.alloc(name, Role::Input)
+    }
+
+    fn mark_public(&mut self, variable: Variable) {
+        self.allocation.roles[variable.0] = Role::Public;
+    }
+
+    fn enforce(&mut self, a: LinearCombination, b: LinearCombination, c: LinearCombination) {
+        self.constraints.push((a, b, c));
+    }
+
+    fn value(&self, _variable: Variable) -> Option<FQ> {
+        None
+    }
+}
+
+/// Records variable assignments and checks every constraint as it is enforced, a
+/// constraint reading an unassigned variable is listed in `unchecked` instead
+pub struct WitnessBuilder {
+    field_modulus: BigInt,
+    allocation: Allocation,
+    values: Vec<Option<FQ>>,
+    num_constraints: usize,
+    /// indices of constraints that did not hold for the assigned values
+    pub unsatisfied: Vec<usize>,
+    /// indices of constraints reading a variable allocated without a value, they
+    /// could not be checked
+    pub unchecked: Vec<usize>,
+}
+
+impl WitnessBuilder {
+    /// fails on a modulus below two
+    pub fn new(field_modulus: impl Into<BigInt>) -> Result<WitnessBuilder, CompileError> {
+        let field_modulus = field_modulus.into();
+        check_modulus(&field_modulus)?;
+        let one = fq(BigInt::one(), &field_modulus);
+        Ok(WitnessBuilder {
+            field_modulus,
+            allocation: Allocation::new(),
+            values: vec![Some(one)],
+            num_constraints: 0,
+            unsatisfied: Vec::new(),
+            unchecked: Vec::new(),
+        })
+    }
+
+    /// the witness in the same layout as `R1CSBuilder::into_r1cs`,
+    /// `None` if a variable was allocated without a value
+    pub fn into_witness(self) -> Option<Vec<FQ>> {
+        let layout = self.allocation.layout();
+        let mut witness = self.values.clone();
+        for (v, index) in layout.into_iter().enumerate() {
+            witness[index] = self.values[v].clone();
+        }
+        witness.into_iter().collect()
+    }
+}
+
+impl ConstraintSystem for WitnessBuilder {
+    fn field_modulus(&self) -> &BigInt {
+        &self.field_modulus
+    }
+
+    fn alloc(&mut self, name: &str, value: Option<FQ>) -> Variable {
+        self.values.push(value);
+        self.allocation.alloc(name, Role::Intermediate)
+    }
+
+    fn alloc_input(&mut self, name: &str, value: Option<FQ>) -> Variable {
+        self.values.push(value);
+        self.allocation.alloc(name, Role::Input)
+    }
+
+    fn mark_public(&mut self, variable: Variable) {
+        self.allocation.roles[variable.0] = Role::Public;
+    }
+
+    fn enforce(&mut self, a: LinearCombination, b: LinearCombination, c: LinearCombination) {
+        match (self.evaluate(&a), self.evaluate(&b), self.evaluate(&c)) {
+            (Some(a), Some(b), Some(c)) => {
+                if a.mul(FqElement::FQ(b)) != c {
+                    self.unsatisfied.push(self.num_constraints);
+                }
+            }
+            _ => self.unchecked.push(self.num_constraints),
+        }
+        self.num_constraints += 1;
+    }
+
+    fn value(&self, variable: Variable) -> Option<FQ> {
+        self.values[variable.0].clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+    use rust_ecc::field::FqElement;
+
+    use super::{ConstraintSystem, R1CSBuilder, Variable, WitnessBuilder};
+    use crate::r1cs::fq;
+
+    /// y = x^3 + x + 5 with a public y
+    fn cubic<CS: ConstraintSystem>(cs: &mut CS, x: Option<i64>) -> Variable {
+        let p = cs.field_modulus().clone();
+        let x_value = x.map(|x| fq(BigInt::from(x), &p));
+        let x = cs.alloc_input("x", x_value);
+        let x_squared = cs.alloc(
+            "x_squared",
+            cs.value(x).map(|x| x.mul(FqElement::FQ(x.clone()))),
+        );
+        cs.enforce(cs.lc(x), cs.lc(x), cs.lc(x_squared));
+        let x_cubed = cs.alloc(
+            "x_cubed",
+            cs.value(x_squared)
+                .zip(cs.value(x))
+                .map(|(a, b)| a.mul(FqElement::FQ(b))),
+        );
+        cs.enforce(cs.lc(x_squared), cs.lc(x), cs.lc(x_cubed));
+        let sum = cs.lc(x_cubed).add(&cs.lc(x)).add(&cs.constant(5.into()));
+        let y = cs.alloc_public("y", cs.evaluate(&sum));
+        cs.enforce(sum, cs.lc(cs.one()), cs.lc(y));
+        y
+    }
+
+    #[test]
+    fn builders_agree_on_layout() {
+        let p = BigInt::from(101);
+        let mut builder = R1CSBuilder::new(p.clone()).unwrap();
+        cubic(&mut builder, None);
+        assert_eq!(
+            builder.variable_names(),
+            ["one", "y", "x", "x_squared", "x_cubed"]
+        );
+        let r1cs = builder.into_r1cs();
+        assert_eq!(r1cs.num_constraints(), 3);
+        assert_eq!(r1cs.public_variables(), 1..2);
+        assert_eq!(r1cs.private_variables(), 2..3);
+
+        let mut prover = WitnessBuilder::new(p.clone()).unwrap();
+        cubic(&mut prover, Some(3));
+        assert!(prover.unsatisfied.is_empty() && prover.unchecked.is_empty());
+        let witness = prover.into_witness().unwrap();
+        assert_eq!(witness[1].n, BigInt::from(35));
+        assert!(r1cs.is_satisfied(&witness));
+    }
+
+    #[test]
+    fn witness_builder_reports_broken_constraints() {
+        let mut cs = WitnessBuilder::new(BigInt::from(101)).unwrap();
+        let p = cs.field_modulus().clone();
+        let a = cs.alloc("a", Some(fq(2.into(), &p)));
+        let b = cs.alloc("b", Some(fq(3.into(), &p)));
+        let c = cs.alloc("c", Some(fq(7.into(), &p)));
+        cs.enforce(cs.lc(a), cs.lc(b), cs.lc(c));
+        cs.enforce(
+            cs.lc(a).add(&cs.lc(b)),
+            cs.lc(cs.one()),
+            cs.constant(5.into()),
+        );
+        assert_eq!(cs.unsatisfied, vec![0]);
+        let unassigned = cs.alloc("d", None);
+        assert!(cs.value(unassigned).is_none());
+        // a constraint on a missing value is recorded rather than passed over
+        cs.enforce(cs.lc(a), cs.lc(b), cs.lc(unassigned));
+        assert_eq!(cs.unsatisfied, vec![0]);
+        assert_eq!(cs.unchecked, vec![2]);
+        assert!(cs.into_witness().is_none());
+    }
+}
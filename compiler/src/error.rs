@@ -0,0 +1,332 @@
+use std::fmt;
+
+use num_bigint::BigInt;
+
+use crate::ast::Span;
+
+/// Everything that can be wrong with circuit source, from a stray character to an
+/// output no constraint depends on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CompileError {
+    /// the source does not follow the grammar
+    Parse {
+        message: String,
+        span: Span,
+    },
+    UndefinedVariable {
+        name: String,
+        span: Span,
+    },
+    UndefinedFunction {
+        name: String,
+        span: Span,
+    },
+    UndefinedTable {
+        name: String,
+        span: Span,
+    },
+    AlreadyDefined {
+        name: String,
+        span: Span,
+    },
+    ArgumentCount {
+        name: String,
+        expected: usize,
+        found: usize,
+        span: Span,
+    },
+    /// a field element used as an array or the other way around
+    TypeMismatch {
+        expected: String,
+        found: String,
+        span: Span,
+    },
+    /// a loop bound, array index or array length that depends on the witness
+    NonConstant {
+        what: &'static str,
+        span: Span,
+    },
+    IndexOutOfBounds {
+        index: BigInt,
+        length: usize,
+        span: Span,
+    },
+    ImmutableAssignment {
+        name: String,
+        span: Span,
+    },
+    RecursiveCall {
+        name: String,
+        span: Span,
+    },
+    MissingReturn {
+        name: String,
+        span: Span,
+    },
+    /// a program that would expand into more than `limit` statements, loop iterations
+    /// and array elements in total
+    ExpansionLimit {
+        limit: usize,
+        span: Span,
+    },
+    /// a table that is not `range`, `xor` or an array of constant entries of one
+    /// width between one and three
+    InvalidTable {
+        message: &'static str,
+        span: Span,
+    },
+    /// a statement that is not allowed where it appears, e.g. an input inside a loop
+    Misplaced {
+        message: &'static str,
+        span: Span,
+    },
+    /// a constant at least the field modulus in absolute value, it would silently wrap
+    /// around when reduced
+    ConstantOutOfRange {
+        value: BigInt,
+        field_modulus: BigInt,
+        span: Span,
+    },
+    /// a gate the chosen backend cannot express, e.g. a lookup lowered to R1CS
+    Unsupported {
+        message: &'static str,
+        span: Span,
+    },
+    /// a field modulus below two, there is no field to compute in
+    InvalidModulus {
+        field_modulus: BigInt,
+    },
+    /// an output wire no gate computes or checks, its value is up to the prover
+    UnconstrainedOutput {
+        name: String,
+        span: Option<Span>,
+    },
+}
+
+impl CompileError {
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            CompileError::Parse { span, .. }
+            | CompileError::UndefinedVariable { span, .. }
+            | CompileError::UndefinedFunction { span, .. }
+            | CompileError::UndefinedTable { span, .. }
+            | CompileError::AlreadyDefined { span, .. }
+            | CompileError::ArgumentCount { span, .. }
+            | CompileError::TypeMismatch { span, .. }
+            | CompileError::NonConstant { span, .. }
+            | CompileError::IndexOutOfBounds { span, .. }
+            | CompileError::ImmutableAssignment { span, .. }
+            | CompileError::RecursiveCall { span, .. }
+            | CompileError::MissingReturn { span, .. }
+            | CompileError::ExpansionLimit { span, .. }
+            | CompileError::InvalidTable { span, .. }
+            | CompileError::Misplaced { span, .. }
+            | CompileError::ConstantOutOfRange { span, .. }
+            | CompileError::Unsupported { span, .. } => Some(*span),
+            CompileError::UnconstrainedOutput { span, .. } => *span,
+            CompileError::InvalidModulus { .. } => None,
+        }
+    }
+
+    /// the message followed by the offending source line with the span underlined
+    pub fn render(&self, source: &str) -> String {
+        match self.span() {
+            Some(span) => render(source, span, &self.to_string()),
+            None => format!("error: {}\n", self),
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompileError::Parse { message, .. } => write!(f, "{}", message),
+            CompileError::UndefinedVariable { name, .. } => {
+                write!(f, "undefined variable `{}`", name)
+            }
+            CompileError::UndefinedFunction { name, .. } => {
+                write!(f, "undefined function `{}`", name)
+            }
+            CompileError::UndefinedTable { name, .. } => write!(f, "undefined table `{}`", name),
+            CompileError::AlreadyDefined { name, .. } => write!(f, "`{}` is already defined", name),
+            CompileError::ArgumentCount {
+                name,
+                expected,
+                found,
+                ..
+            } => write!(
+                f,
+                "`{}` takes {} argument(s), found {}",
+                name, expected, found
+            ),
+            CompileError::TypeMismatch {
+                expected, found, ..
+            } => write!(f, "expected {}, found {}", expected, found),
+            CompileError::NonConstant { what, .. } => {
+                write!(f, "{} must be known at compile time", what)
+            }
+            CompileError::IndexOutOfBounds { index, length, .. } => write!(
+                f,
+                "index {} is out of bounds for an array of length {}",
+                index, length
+            ),
+            CompileError::ImmutableAssignment { name, .. } => {
+                write!(f, "cannot assign twice to immutable variable `{}`", name)
+            }
+            CompileError::RecursiveCall { name, .. } => write!(f, "recursive call to `{}`", name),
+            CompileError::MissingReturn { name, .. } => {
+                write!(f, "function `{}` does not return a value", name)
+            }
+            CompileError::ExpansionLimit { limit, .. } => write!(
+                f,
+                "the program expands into more than {} statements, iterations and array elements",
+                limit
+            ),
+            CompileError::InvalidTable { message, .. }
+            | CompileError::Misplaced { message, .. }
+            | CompileError::Unsupported { message, .. } => write!(f, "{}", message),
+            CompileError::ConstantOutOfRange {
+                value,
+                field_modulus,
+                ..
+            } => write!(
+                f,
+                "constant {} does not fit in the field of size {}",
+                value, field_modulus
+            ),
+            CompileError::InvalidModulus { field_modulus } => write!(
+                f,
+                "the field modulus must be greater than one, found {}",
+                field_modulus
+            ),
+            CompileError::UnconstrainedOutput { name, .. } => {
+                write!(f, "output `{}` is not constrained", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// 1-based line and column of a byte offset
+pub fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..char_boundary(source, offset)];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+/// Render `message` with the line containing `span` and carets under the span
+///
+/// ```text
+/// error: undefined variable `b`
+///  --> 2:9
+///   |
+/// 2 | let a = b;
+///   |         ^
+/// ```
+pub fn render(source: &str, span: Span, message: &str) -> String {
+    let (line, column) = line_column(source, span.start);
+    let text = source.lines().nth(line - 1).unwrap_or("");
+    // spans running past the end of the line are cut off there, reversed spans are
+    // rendered as a single caret
+    let start = char_boundary(source, span.start);
+    let end = char_boundary(source, span.end).max(start);
+    let width = source[start..end]
+        .lines()
+        .next()
+        .map_or(0, |s| s.chars().count())
+        .max(1);
+    let gutter = " ".repeat(line.to_string().len());
+    format!(
+        "error: {}\n{}--> {}:{}\n{} |\n{} | {}\n{} | {}{}\n",
+        message,
+        gutter,
+        line,
+        column,
+        gutter,
+        line,
+        text,
+        gutter,
+        " ".repeat(column - 1),
+        "^".repeat(width)
+    )
+}
+
+/// `offset` clamped to the source and moved back to the start of the character it
+/// points into, spans from outside the lexer may point anywhere
+fn char_boundary(source: &str, offset: usize) -> usize {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{line_column, render, CompileError};
+    use crate::ast::{Span, Visibility};
+    use crate::circuit::{Circuit, Gate, GateKind, Hint, Operand, WireKind};
+    use crate::{flatten, parse};
+
+    #[test]
+    fn carets_under_the_span() {
+        let source = "private a;\nlet b = a * c;";
+        let err = flatten(&parse(source).unwrap()).unwrap_err();
+        assert!(matches!(err, CompileError::UndefinedVariable { ref name, .. } if name == "c"));
+        assert_eq!(
+            err.render(source),
+            "error: undefined variable `c`\n --> 2:13\n  |\n2 | let b = a * c;\n  |             ^\n"
+        );
+
+        let source = "let x = 1\n";
+        let err = parse(source).unwrap_err();
+        // end of input points just past the last line
+        assert_eq!(line_column(source, err.span().unwrap().start), (2, 1));
+        assert!(err.render(source).ends_with("2 | \n  | ^\n"));
+
+        // spans inside a character or reversed do not panic
+        let source = "let € = 1;";
+        assert_eq!(line_column(source, 6), (1, 5));
+        assert!(render(source, Span::new(9, 5), "reversed").ends_with("  |        ^\n"));
+        assert!(render(source, Span::new(5, 6), "inside").ends_with("  |     ^\n"));
+    }
+
+    #[test]
+    fn typed_errors() {
+        let err = flatten(&parse("private n;\nfor i in 0..n { }").unwrap()).unwrap_err();
+        assert!(matches!(
+            err,
+            CompileError::NonConstant {
+                what: "loop bound",
+                ..
+            }
+        ));
+        let source = "private xs[2];\nlet y = xs * 2;";
+        let err = flatten(&parse(source).unwrap()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected a field element, found an array of length 2"
+        );
+        assert!(err
+            .render(source)
+            .contains("2 | let y = xs * 2;\n  |         ^^\n"));
+
+        // a root the prover picks without any gate checking it
+        let mut circuit = Circuit::default();
+        let x = circuit.new_wire(Some("x".into()), WireKind::Input(Visibility::Private));
+        let root = circuit.new_wire(Some("root".into()), WireKind::Output);
+        circuit.gates.push(Gate {
+            kind: GateKind::Hint {
+                hint: Hint::Sqrt,
+                args: vec![Operand::Wire(x)],
+                out: root,
+            },
+            span: Span::new(0, 4),
+        });
+        let err = circuit.check_outputs().unwrap_err();
+        assert_eq!(err.to_string(), "output `root` is not constrained");
+        assert_eq!(err.span(), Some(Span::new(0, 4)));
+    }
+}
@@ -0,0 +1,1044 @@
+use std::collections::{HashMap, HashSet};
+
+use num_bigint::BigInt;
+use num_traits::{One, Signed, ToPrimitive};
+
+use crate::ast::{
+    BinaryOp, Expr, ExprKind, Function, Ident, Program, Span, Statement, StatementKind,
+};
+use crate::circuit::Hint;
+use crate::error::CompileError;
+use crate::plonk::LookupTable;
+
+/// Statements, loop iterations, array elements and table entries `expand` produces at
+/// most, summed over the whole program
+pub const MAX_EXPANSION: usize = 1 << 20;
+
+/// Inline function calls, unroll loops and split arrays into one binding per element
+///
+/// The result is straight-line code with only inputs, `let`, `output`, `assert_eq`,
+/// tables and lookups over field elements, table values are reduced to `range(n)`,
+/// `xor(n)` or an array of constant entries. Input names are kept, array elements are named `xs[0]` and
+/// any name that is bound again, by reassignment, a loop iteration or a second call,
+/// gets a `#n` suffix. Bindings inside an inlined function always get one, so they
+/// never take a name from the top level.
+pub fn expand(program: &Program) -> Result<Program, CompileError> {
+    let mut expander = Expander {
+        functions: HashMap::new(),
+        frames: vec![Frame::default()],
+        used: HashSet::new(),
+        expansion: 0,
+        tables: HashMap::new(),
+        calls: Vec::new(),
+        statements: Vec::new(),
+    };
+    // reserved up front, a binding before the declaration must not take the name
+    for statement in &program.statements {
+        if let StatementKind::Input { name, size, .. } = &statement.kind {
+            expander.spend(&BigInt::from(size.unwrap_or(1)), statement.span)?;
+            expander.used.extend(input_names(name, *size));
+        }
+    }
+    for function in &program.functions {
+        let name = function.name.name.as_str();
+        if expander.functions.insert(name, function).is_some() {
+            return Err(CompileError::AlreadyDefined {
+                name: name.to_string(),
+                span: function.name.span,
+            });
+        }
+    }
+    for statement in &program.statements {
+        expander.statement(statement)?;
+    }
+    Ok(Program {
+        functions: Vec::new(),
+        statements: expander.statements,
+    })
+}
+
+#[derive(Clone, Debug)]
+enum Binding {
+    /// loop variables and constant function arguments
+    Constant(BigInt),
+    Scalar {
+        name: String,
+        mutable: bool,
+    },
+    Array {
+        names: Vec<String>,
+        mutable: bool,
+    },
+}
+
+impl Binding {
+    fn with_mutable(self, mutable: bool) -> Binding {
+        match self {
+            Binding::Scalar { name, .. } => Binding::Scalar { name, mutable },
+            Binding::Array { names, .. } => Binding::Array { names, mutable },
+            constant => constant,
+        }
+    }
+}
+
+/// an expanded expression, arrays only exist until they are split into elements
+enum Value {
+    Scalar(Expr),
+    Array(Vec<Expr>),
+}
+
+impl Value {
+    fn describe(&self) -> String {
+        match self {
+            Value::Scalar(_) => "a field element".to_string(),
+            Value::Array(elements) => format!("an array of length {}", elements.len()),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Frame {
+    bindings: HashMap<String, Binding>,
+    /// a function body does not see the bindings of its caller
+    function: bool,
+}
+
+struct Expander<'a> {
+    functions: HashMap<&'a str, &'a Function>,
+    frames: Vec<Frame>,
+    /// every expanded binding gets a name nobody else has
+    used: HashSet<String>,
+    /// work done so far, up to `MAX_EXPANSION`
+    expansion: usize,
+    /// entry width of every declared table
+    tables: HashMap<String, usize>,
+    /// functions currently being inlined, to reject recursion
+    calls: Vec<&'a str>,
+    statements: Vec<Statement>,
+}
+
+impl<'a> Expander<'a> {
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        for frame in self.frames.iter().rev() {
+            if let Some(binding) = frame.bindings.get(name) {
+                return Some(binding);
+            }
+            if frame.function {
+                break;
+            }
+        }
+        None
+    }
+
+    fn lookup_mut(&mut self, name: &str) -> Option<&mut Binding> {
+        for frame in self.frames.iter_mut().rev() {
+            if frame.bindings.contains_key(name) {
+                return frame.bindings.get_mut(name);
+            }
+            if frame.function {
+                break;
+            }
+        }
+        None
+    }
+
+    fn check_free(&self, name: &Ident) -> Result<(), CompileError> {
+        match self.lookup(&name.name) {
+            Some(_) => Err(CompileError::AlreadyDefined {
+                name: name.name.clone(),
+                span: name.span,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn define(&mut self, name: &Ident, binding: Binding) {
+        let frame = self
+            .frames
+            .last_mut()
+            .expect("the top-level frame is never popped");
+        frame.bindings.insert(name.name.clone(), binding);
+    }
+
+    /// count `amount` against `MAX_EXPANSION` before producing that much
+    fn spend(&mut self, amount: &BigInt, span: Span) -> Result<(), CompileError> {
+        self.expansion = amount
+            .to_usize()
+            .and_then(|amount| self.expansion.checked_add(amount))
+            .filter(|total| *total <= MAX_EXPANSION)
+            .ok_or(CompileError::ExpansionLimit {
+                limit: MAX_EXPANSION,
+                span,
+            })?;
+        Ok(())
+    }
+
+    fn fresh(&mut self, base: &str) -> String {
+        let mut n = usize::from(!self.calls.is_empty());
+        loop {
+            let name = match n {
+                0 => base.to_string(),
+                n => format!("{}#{}", base, n),
+            };
+            if self.used.insert(name.clone()) {
+                return name;
+            }
+            n += 1;
+        }
+    }
+
+    /// emit `let` (or `output`) for a single element, returns the name it got
+    fn store(
+        &mut self,
+        base: &str,
+        value: Expr,
+        output: bool,
+        name_span: Span,
+        span: Span,
+    ) -> String {
+        let name = Ident {
+            name: self.fresh(base),
+            span: name_span,
+        };
+        let kind = if output {
+            StatementKind::Output {
+                name: name.clone(),
+                value,
+            }
+        } else {
+            StatementKind::Let {
+                name: name.clone(),
+                mutable: false,
+                value,
+            }
+        };
+        self.statements.push(Statement { kind, span });
+        name.name
+    }
+
+    /// emit one `let` (or `output`) per element of `value`
+    fn store_value(
+        &mut self,
+        name: &Ident,
+        value: Value,
+        output: bool,
+        span: Span,
+    ) -> Result<Binding, CompileError> {
+        if let Value::Array(elements) = &value {
+            self.spend(&BigInt::from(elements.len()), span)?;
+        }
+        Ok(match value {
+            Value::Scalar(expr) => Binding::Scalar {
+                name: self.store(&name.name, expr, output, name.span, span),
+                mutable: false,
+            },
+            Value::Array(elements) => Binding::Array {
+                names: elements
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, expr)| {
+                        let base = format!("{}[{}]", name.name, i);
+                        self.store(&base, expr, output, name.span, span)
+                    })
+                    .collect(),
+                mutable: false,
+            },
+        })
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result<(), CompileError> {
+        let span = statement.span;
+        self.spend(&BigInt::one(), span)?;
+        match &statement.kind {
+            StatementKind::Input {
+                visibility,
+                name,
+                size,
+            } => {
+                if self.frames.len() > 1 {
+                    return Err(CompileError::Misplaced {
+                        message: "inputs must be declared at the top level",
+                        span,
+                    });
+                }
+                self.check_free(name)?;
+                // the names were reserved by `expand`
+                let mut names = input_names(name, *size);
+                for element in &names {
+                    let element = Ident {
+                        name: element.clone(),
+                        span: name.span,
+                    };
+                    self.statements.push(Statement {
+                        kind: StatementKind::Input {
+                            visibility: *visibility,
+                            name: element,
+                            size: None,
+                        },
+                        span,
+                    });
+                }
+                let binding = match size {
+                    None => Binding::Scalar {
+                        name: names.remove(0),
+                        mutable: false,
+                    },
+                    Some(_) => Binding::Array {
+                        names,
+                        mutable: false,
+                    },
+                };
+                self.define(name, binding);
+            }
+            StatementKind::Let {
+                name,
+                mutable,
+                value,
+            } => {
+                self.check_free(name)?;
+                let value = self.value(value)?;
+                let binding = self.store_value(name, value, false, span)?;
+                self.define(name, binding.with_mutable(*mutable));
+            }
+            StatementKind::Output { name, value } => {
+                if self.frames.iter().any(|frame| frame.function) {
+                    return Err(CompileError::Misplaced {
+                        message: "outputs must be declared at the top level",
+                        span,
+                    });
+                }
+                self.check_free(name)?;
+                let value = self.value(value)?;
+                let binding = self.store_value(name, value, true, span)?;
+                self.define(name, binding);
+            }
+            StatementKind::AssertEq { lhs, rhs } => {
+                let lhs = self.scalar(lhs)?;
+                let rhs = self.scalar(rhs)?;
+                self.statements.push(Statement {
+                    kind: StatementKind::AssertEq { lhs, rhs },
+                    span,
+                });
+            }
+            StatementKind::Assign { name, index, value } => {
+                self.assign(name, index, value, span)?
+            }
+            StatementKind::For {
+                var,
+                start,
+                end,
+                body,
+            } => {
+                self.check_free(var)?;
+                let mut i = self.constant(start, "loop bound")?;
+                let end = self.constant(end, "loop bound")?;
+                if end > i {
+                    self.spend(&(&end - &i), span)?;
+                }
+                while i < end {
+                    let mut frame = Frame::default();
+                    frame
+                        .bindings
+                        .insert(var.name.clone(), Binding::Constant(i.clone()));
+                    self.frames.push(frame);
+                    for statement in body {
+                        self.statement(statement)?;
+                    }
+                    self.frames.pop();
+                    i += 1;
+                }
+            }
+            StatementKind::Table { name, value } => {
+                if self.frames.len() > 1 {
+                    return Err(CompileError::Misplaced {
+                        message: "tables must be declared at the top level",
+                        span,
+                    });
+                }
+                if self.tables.contains_key(&name.name) {
+                    return Err(CompileError::AlreadyDefined {
+                        name: name.name.clone(),
+                        span: name.span,
+                    });
+                }
+                let (value, width) = self.table(value)?;
+                self.tables.insert(name.name.clone(), width);
+                self.statements.push(Statement {
+                    kind: StatementKind::Table {
+                        name: name.clone(),
+                        value,
+                    },
+                    span,
+                });
+            }
+            StatementKind::Lookup { table, args } => {
+                let width =
+                    *self
+                        .tables
+                        .get(&table.name)
+                        .ok_or_else(|| CompileError::UndefinedTable {
+                            name: table.name.clone(),
+                            span: table.span,
+                        })?;
+                if args.len() != width {
+                    return Err(CompileError::ArgumentCount {
+                        name: table.name.clone(),
+                        expected: width,
+                        found: args.len(),
+                        span,
+                    });
+                }
+                let args = args
+                    .iter()
+                    .map(|arg| self.scalar(arg))
+                    .collect::<Result<Vec<Expr>, CompileError>>()?;
+                self.statements.push(Statement {
+                    kind: StatementKind::Lookup {
+                        table: table.clone(),
+                        args,
+                    },
+                    span,
+                });
+            }
+            StatementKind::Return { .. } => {
+                return Err(CompileError::Misplaced {
+                    message: "`return` must be the last statement of a function",
+                    span,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// reduce the value of a `table` statement to `range(n)`, `xor(n)` or an array of
+    /// constant entries, returns it with the number of values per entry
+    fn table(&mut self, value: &Expr) -> Result<(Expr, usize), CompileError> {
+        let span = value.span;
+        let invalid = |message| CompileError::InvalidTable { message, span };
+        let constant = |n| Expr {
+            kind: ExprKind::Constant(n),
+            span,
+        };
+        match &value.kind {
+            ExprKind::Call { name, args } if matches!(name.name.as_str(), "range" | "xor") => {
+                let [bits] = args.as_slice() else {
+                    return Err(CompileError::ArgumentCount {
+                        name: name.name.clone(),
+                        expected: 1,
+                        found: args.len(),
+                        span,
+                    });
+                };
+                let bits = self.constant(bits, "table size")?;
+                if bits.is_negative() {
+                    return Err(invalid("a table size is a number of bits"));
+                }
+                let range = name.name == "range";
+                // `range` lists 2^bits entries and `xor` 4^bits
+                let log2 = bits
+                    .to_u32()
+                    .and_then(|bits| {
+                        if range {
+                            Some(bits)
+                        } else {
+                            bits.checked_mul(2)
+                        }
+                    })
+                    .filter(|log2| *log2 < u64::BITS)
+                    .ok_or(CompileError::ExpansionLimit {
+                        limit: MAX_EXPANSION,
+                        span,
+                    })?;
+                self.spend(&(BigInt::one() << log2), span)?;
+                let value = Expr {
+                    kind: ExprKind::Call {
+                        name: name.clone(),
+                        args: vec![constant(bits)],
+                    },
+                    span,
+                };
+                Ok((value, if range { 1 } else { 3 }))
+            }
+            ExprKind::Array(entries) => {
+                self.spend(&BigInt::from(entries.len()), span)?;
+                let mut rows: Vec<Vec<BigInt>> = Vec::new();
+                for entry in entries {
+                    rows.push(match &entry.kind {
+                        ExprKind::Array(values) => values
+                            .iter()
+                            .map(|v| self.constant(v, "table entry"))
+                            .collect::<Result<Vec<BigInt>, CompileError>>()?,
+                        _ => vec![self.constant(entry, "table entry")?],
+                    });
+                }
+                LookupTable::check(&rows).map_err(invalid)?;
+                let width = rows[0].len();
+                let entries = rows
+                    .into_iter()
+                    .map(|values| Expr {
+                        kind: ExprKind::Array(values.into_iter().map(constant).collect()),
+                        span,
+                    })
+                    .collect();
+                let value = Expr {
+                    kind: ExprKind::Array(entries),
+                    span,
+                };
+                Ok((value, width))
+            }
+            _ => Err(invalid(
+                "a table is `range(bits)`, `xor(bits)` or an array of constant entries",
+            )),
+        }
+    }
+
+    fn assign(
+        &mut self,
+        name: &Ident,
+        index: &Option<Expr>,
+        value: &Expr,
+        span: Span,
+    ) -> Result<(), CompileError> {
+        let value = self.value(value)?;
+        let binding = self
+            .lookup(&name.name)
+            .cloned()
+            .ok_or_else(|| undefined(name))?;
+        let updated = match (binding, index) {
+            (Binding::Scalar { mutable: true, .. }, None) => match value {
+                Value::Scalar(_) => self.store_value(name, value, false, span)?,
+                value => return Err(mismatch("a field element", &value, span)),
+            },
+            (
+                Binding::Array {
+                    names,
+                    mutable: true,
+                },
+                None,
+            ) => match value {
+                Value::Array(elements) if elements.len() == names.len() => {
+                    self.store_value(name, Value::Array(elements), false, span)?
+                }
+                value => {
+                    let expected = format!("an array of length {}", names.len());
+                    return Err(mismatch(&expected, &value, span));
+                }
+            },
+            (
+                Binding::Array {
+                    mut names,
+                    mutable: true,
+                },
+                Some(index),
+            ) => {
+                let i = self.index(index, names.len())?;
+                let Value::Scalar(value) = value else {
+                    return Err(mismatch("a field element", &value, span));
+                };
+                let base = format!("{}[{}]", name.name, i);
+                names[i] = self.store(&base, value, false, name.span, span);
+                Binding::Array {
+                    names,
+                    mutable: true,
+                }
+            }
+            (Binding::Scalar { mutable: true, .. }, Some(_)) => {
+                return Err(CompileError::TypeMismatch {
+                    expected: "an array".to_string(),
+                    found: "a field element".to_string(),
+                    span: name.span,
+                })
+            }
+            _ => {
+                return Err(CompileError::ImmutableAssignment {
+                    name: name.name.clone(),
+                    span: name.span,
+                })
+            }
+        };
+        *self
+            .lookup_mut(&name.name)
+            .expect("the binding was found above") = updated.with_mutable(true);
+        Ok(())
+    }
+
+    /// evaluate an expression that has to be known at compile time
+    fn constant(&self, expr: &Expr, what: &'static str) -> Result<BigInt, CompileError> {
+        match &expr.kind {
+            ExprKind::Constant(n) => Ok(n.clone()),
+            ExprKind::Variable(ident) => match self.lookup(&ident.name) {
+                Some(Binding::Constant(n)) => Ok(n.clone()),
+                Some(_) => Err(CompileError::NonConstant {
+                    what,
+                    span: ident.span,
+                }),
+                None => Err(undefined(ident)),
+            },
+            ExprKind::Neg(inner) => Ok(-self.constant(inner, what)?),
+            ExprKind::Binary { op, lhs, rhs } if *op != BinaryOp::Div => {
+                let (lhs, rhs) = (self.constant(lhs, what)?, self.constant(rhs, what)?);
+                Ok(match op {
+                    BinaryOp::Add => lhs + rhs,
+                    BinaryOp::Sub => lhs - rhs,
+                    _ => lhs * rhs,
+                })
+            }
+            _ => Err(CompileError::NonConstant {
+                what,
+                span: expr.span,
+            }),
+        }
+    }
+
+    fn index(&self, index: &Expr, len: usize) -> Result<usize, CompileError> {
+        let i = self.constant(index, "array index")?;
+        i.to_usize()
+            .filter(|i| *i < len)
+            .ok_or_else(|| CompileError::IndexOutOfBounds {
+                index: i.clone(),
+                length: len,
+                span: index.span,
+            })
+    }
+
+    fn scalar(&mut self, expr: &Expr) -> Result<Expr, CompileError> {
+        match self.value(expr)? {
+            Value::Scalar(expr) => Ok(expr),
+            value => Err(mismatch("a field element", &value, expr.span)),
+        }
+    }
+
+    fn value(&mut self, expr: &Expr) -> Result<Value, CompileError> {
+        let span = expr.span;
+        let scalar = |kind| Ok(Value::Scalar(Expr { kind, span }));
+        match &expr.kind {
+            ExprKind::Constant(_) => Ok(Value::Scalar(expr.clone())),
+            ExprKind::Variable(ident) => match self.lookup(&ident.name) {
+                None => Err(undefined(ident)),
+                Some(Binding::Constant(n)) => scalar(ExprKind::Constant(n.clone())),
+                Some(Binding::Scalar { name, .. }) => Ok(Value::Scalar(variable(name, ident.span))),
+                Some(Binding::Array { names, .. }) => Ok(Value::Array(
+                    names
+                        .iter()
+                        .map(|name| variable(name, ident.span))
+                        .collect(),
+                )),
+            },
+            ExprKind::Neg(inner) => scalar(ExprKind::Neg(Box::new(self.scalar(inner)?))),
+            ExprKind::Binary { op, lhs, rhs } => scalar(ExprKind::Binary {
+                op: *op,
+                lhs: Box::new(self.scalar(lhs)?),
+                rhs: Box::new(self.scalar(rhs)?),
+            }),
+            ExprKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => scalar(ExprKind::If {
+                cond: Box::new(self.scalar(cond)?),
+                then_branch: Box::new(self.scalar(then_branch)?),
+                else_branch: Box::new(self.scalar(else_branch)?),
+            }),
+            ExprKind::Call { name, args } => {
+                let function = self.functions.get(name.name.as_str()).copied();
+                match function {
+                    Some(function) => {
+                        let args = args.iter().map(|arg| self.value(arg)).collect::<Result<
+                            Vec<Value>,
+                            CompileError,
+                        >>(
+                        )?;
+                        self.inline(function, args, span)
+                    }
+                    None => {
+                        let hint = Hint::builtin(&name.name).ok_or_else(|| {
+                            CompileError::UndefinedFunction {
+                                name: name.name.clone(),
+                                span: name.span,
+                            }
+                        })?;
+                        if args.len() != hint.arity() {
+                            return Err(CompileError::ArgumentCount {
+                                name: name.name.clone(),
+                                expected: hint.arity(),
+                                found: args.len(),
+                                span,
+                            });
+                        }
+                        let args = args.iter().map(|arg| self.scalar(arg)).collect::<Result<
+                            Vec<Expr>,
+                            CompileError,
+                        >>(
+                        )?;
+                        scalar(ExprKind::Call {
+                            name: name.clone(),
+                            args,
+                        })
+                    }
+                }
+            }
+            ExprKind::Array(elements) => Ok(Value::Array(
+                elements
+                    .iter()
+                    .map(|element| self.scalar(element))
+                    .collect::<Result<Vec<Expr>, CompileError>>()?,
+            )),
+            ExprKind::Repeat { value, count } => {
+                let value = self.scalar(value)?;
+                let count = self.constant(count, "array length")?;
+                if count.is_positive() {
+                    self.spend(&count, span)?;
+                }
+                let count = count.to_usize().ok_or_else(|| CompileError::TypeMismatch {
+                    expected: "an array length".to_string(),
+                    found: count.to_string(),
+                    span,
+                })?;
+                Ok(Value::Array(vec![value; count]))
+            }
+            ExprKind::Index { array, index } => match self.value(array)? {
+                Value::Array(mut elements) => {
+                    let i = self.index(index, elements.len())?;
+                    Ok(Value::Scalar(elements.swap_remove(i)))
+                }
+                value => Err(mismatch("an array", &value, array.span)),
+            },
+        }
+    }
+
+    /// expand the body of `function` in place, returns the value of its `return`
+    fn inline(
+        &mut self,
+        function: &'a Function,
+        args: Vec<Value>,
+        span: Span,
+    ) -> Result<Value, CompileError> {
+        let name = function.name.name.as_str();
+        if args.len() != function.params.len() {
+            return Err(CompileError::ArgumentCount {
+                name: name.to_string(),
+                expected: function.params.len(),
+                found: args.len(),
+                span,
+            });
+        }
+        if self.calls.contains(&name) {
+            return Err(CompileError::RecursiveCall {
+                name: name.to_string(),
+                span,
+            });
+        }
+        self.calls.push(name);
+        let mut frame = Frame {
+            bindings: HashMap::new(),
+            function: true,
+        };
+        for (param, arg) in function.params.iter().zip(args) {
+            let binding = self.argument(param, arg, span);
+            if frame.bindings.insert(param.name.clone(), binding).is_some() {
+                return Err(CompileError::AlreadyDefined {
+                    name: param.name.clone(),
+                    span: param.span,
+                });
+            }
+        }
+        self.frames.push(frame);
+        let Some((last, body)) = function.body.split_last() else {
+            return Err(missing_return(name, span));
+        };
+        for statement in body {
+            self.statement(statement)?;
+        }
+        let value = match &last.kind {
+            StatementKind::Return { value } => self.value(value)?,
+            _ => {
+                self.statement(last)?;
+                return Err(missing_return(name, span));
+            }
+        };
+        self.frames.pop();
+        self.calls.pop();
+        Ok(value)
+    }
+
+    /// bind a parameter without copying variables, literals stay compile-time constants
+    fn argument(&mut self, param: &Ident, arg: Value, span: Span) -> Binding {
+        let element = |this: &mut Self, base: String, expr: Expr| match expr.kind {
+            ExprKind::Variable(ident) => ident.name,
+            _ => this.store(&base, expr, false, param.span, span),
+        };
+        match arg {
+            Value::Scalar(expr) => match literal(&expr) {
+                Some(n) => Binding::Constant(n),
+                None => Binding::Scalar {
+                    name: element(self, param.name.clone(), expr),
+                    mutable: false,
+                },
+            },
+            Value::Array(elements) => Binding::Array {
+                names: elements
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, expr)| element(self, format!("{}[{}]", param.name, i), expr))
+                    .collect(),
+                mutable: false,
+            },
+        }
+    }
+}
+
+/// The table a `table` statement of an expanded program declares
+///
+/// Panics on a value `expand` did not reduce.
+pub fn lookup_table(name: &Ident, value: &Expr) -> LookupTable {
+    let constant = |expr: &Expr| match &expr.kind {
+        ExprKind::Constant(n) => n.clone(),
+        _ => panic!("table entries are constants after expand"),
+    };
+    match &value.kind {
+        ExprKind::Call {
+            name: builtin,
+            args,
+        } => {
+            let bits = constant(&args[0])
+                .to_usize()
+                .expect("table sizes are bounded by expand");
+            let table = match builtin.name.as_str() {
+                "range" => LookupTable::range(bits),
+                _ => LookupTable::xor(bits),
+            };
+            LookupTable {
+                name: name.name.clone(),
+                ..table
+            }
+        }
+        ExprKind::Array(entries) => LookupTable::new(
+            &name.name,
+            entries
+                .iter()
+                .map(|entry| match &entry.kind {
+                    ExprKind::Array(values) => values.iter().map(constant).collect(),
+                    _ => vec![constant(entry)],
+                })
+                .collect(),
+        ),
+        _ => panic!("table values are reduced by expand"),
+    }
+}
+
+/// element names of an input, `xs[0]`, `xs[1]`, ... for an array
+fn input_names(name: &Ident, size: Option<usize>) -> Vec<String> {
+    match size {
+        None => vec![name.name.clone()],
+        Some(size) => (0..size).map(|i| format!("{}[{}]", name.name, i)).collect(),
+    }
+}
+
+/// value of an expression made of integer literals only
+fn literal(expr: &Expr) -> Option<BigInt> {
+    match &expr.kind {
+        ExprKind::Constant(n) => Some(n.clone()),
+        ExprKind::Neg(inner) => Some(-literal(inner)?),
+        ExprKind::Binary { op, lhs, rhs } => {
+            let (lhs, rhs) = (literal(lhs)?, literal(rhs)?);
+            match op {
+                BinaryOp::Add => Some(lhs + rhs),
+                BinaryOp::Sub => Some(lhs - rhs),
+                BinaryOp::Mul => Some(lhs * rhs),
+                BinaryOp::Div => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn variable(name: &str, span: Span) -> Expr {
+    Expr {
+        kind: ExprKind::Variable(Ident {
+            name: name.to_string(),
+            span,
+        }),
+        span,
+    }
+}
+
+fn undefined(ident: &Ident) -> CompileError {
+    CompileError::UndefinedVariable {
+        name: ident.name.clone(),
+        span: ident.span,
+    }
+}
+
+fn mismatch(expected: &str, found: &Value, span: Span) -> CompileError {
+    CompileError::TypeMismatch {
+        expected: expected.to_string(),
+        found: found.describe(),
+        span,
+    }
+}
+
+fn missing_return(name: &str, span: Span) -> CompileError {
+    CompileError::MissingReturn {
+        name: name.to_string(),
+        span,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use super::expand;
+    use crate::ast::StatementKind;
+    use crate::test_utils::ints;
+    use crate::{evaluate, flatten, generate_witness, parse, R1CS};
+
+    fn expand_error(source: &str) -> String {
+        expand(&parse(source).unwrap()).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn loops_unroll_into_straight_line_code() {
+        let source = "private xs[3];\nlet mut acc = 0;\nfor i in 0..3 { acc = acc + xs[i] * xs[i]; }\noutput s = acc;";
+        let circuit = flatten(&parse(source).unwrap()).unwrap();
+        assert_eq!(
+            circuit.to_string(),
+            "w3 = xs[0] * xs[0]\nacc#1 = 0 + w3\nw5 = xs[1] * xs[1]\nacc#2 = acc#1 + w5\nw7 = xs[2] * xs[2]\ns = acc#2 + w7\n"
+        );
+    }
+
+    #[test]
+    fn functions_are_inlined() {
+        let source = "fn square(x) { return x * x; }\n\
+                      fn sum_squares(v, n) {\n  let mut acc = 0;\n  for i in 0..n { acc = acc + square(v[i]); }\n  return acc;\n}\n\
+                      fn rotate(v) { return [v[1], v[2], v[0]]; }\n\
+                      public a;\nprivate b, c;\n\
+                      let mut r = rotate([a, b, c]);\nr[0] = r[0] + 1;\n\
+                      output s = sum_squares(r, 3);\noutput t = r;";
+        let program = parse(source).unwrap();
+        let expanded = expand(&program).unwrap();
+        assert!(expanded.statements.iter().all(|statement| matches!(
+            statement.kind,
+            StatementKind::Input { .. }
+                | StatementKind::Let { .. }
+                | StatementKind::Output { .. }
+                | StatementKind::AssertEq { .. }
+        )));
+
+        let circuit = flatten(&program).unwrap();
+        let p = BigInt::from(1009);
+        let r1cs = R1CS::from_circuit(&circuit, p.clone()).unwrap();
+        let (public, private) = (ints(&[2]), ints(&[3, 4]));
+        let outputs = evaluate(&program, &public, &private, p.clone()).unwrap();
+        let values: Vec<(&str, BigInt)> = outputs
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.n.clone()))
+            .collect();
+        // r = [b + 1, c, a]
+        assert_eq!(
+            values,
+            vec![
+                ("s", BigInt::from(16 + 16 + 4)),
+                ("t[0]", 4.into()),
+                ("t[1]", 4.into()),
+                ("t[2]", 2.into())
+            ]
+        );
+        let witness = generate_witness(&circuit, &r1cs, &public, &private).unwrap();
+        for (wire, variable) in circuit.outputs().iter().zip(r1cs.output_variables()) {
+            let name = circuit.wire_name(*wire);
+            let (_, expected) = outputs.iter().find(|(n, _)| *n == name).unwrap();
+            assert_eq!(&witness[variable], expected);
+        }
+    }
+
+    #[test]
+    fn inlined_locals_leave_input_names_alone() {
+        let source =
+            "fn f(x) { let a = x * x; return a; }\nlet y = f(2);\nprivate a;\noutput a2 = a * y;";
+        let expanded = expand(&parse(source).unwrap()).unwrap();
+        let names: Vec<&str> = expanded
+            .statements
+            .iter()
+            .map(|statement| match &statement.kind {
+                StatementKind::Input { name, .. }
+                | StatementKind::Let { name, .. }
+                | StatementKind::Output { name, .. } => name.name.as_str(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(names, ["a#1", "y", "a", "a2"]);
+    }
+
+    #[test]
+    fn compile_time_errors() {
+        assert_eq!(
+            expand_error("private n;\nfor i in 0..n { }"),
+            "loop bound must be known at compile time"
+        );
+        assert_eq!(
+            expand_error("private xs[2];\nlet y = xs[2];"),
+            "index 2 is out of bounds for an array of length 2"
+        );
+        assert_eq!(
+            expand_error("private xs[2];\nlet y = xs + 1;"),
+            "expected a field element, found an array of length 2"
+        );
+        assert_eq!(
+            expand_error("fn f(x) { return f(x); }\nlet y = f(1);"),
+            "recursive call to `f`"
+        );
+        assert_eq!(
+            expand_error("let x = 1;\nx = 2;"),
+            "cannot assign twice to immutable variable `x`"
+        );
+        assert_eq!(
+            expand_error("fn f(x) { let y = x; }\nlet z = f(1);"),
+            "function `f` does not return a value"
+        );
+        for (source, message) in [
+            ("private x;\nlookup(t, x);", "undefined table `t`"),
+            (
+                "table t = range(8);\ntable t = [1];",
+                "`t` is already defined",
+            ),
+            (
+                "table t = xor(2);\nprivate x;\nlookup(t, x);",
+                "`t` takes 3 argument(s), found 1",
+            ),
+            ("table t = [[1, 2], [3]];", "table entries differ in width"),
+            (
+                "table t = [[1, 2, 3, 4]];",
+                "a table needs entries of one to three values",
+            ),
+            (
+                "private x;\ntable t = [x];",
+                "table entry must be known at compile time",
+            ),
+            (
+                "table t = sqrt(4);",
+                "a table is `range(bits)`, `xor(bits)` or an array of constant entries",
+            ),
+            (
+                "for i in 0..2 { table t = [i]; }",
+                "tables must be declared at the top level",
+            ),
+        ] {
+            assert_eq!(expand_error(source), message);
+        }
+        for source in [
+            "for i in 0..100000000000000000000000 { }",
+            "for i in 0..1000 { for j in 0..1048000 { } }",
+            "let xs = [0; 10000000000];",
+            "private xs[10000000000];",
+            "let xs = [0; 400000];\nlet ys = xs;",
+            "table t = range(21);",
+            "table t = xor(100000000000);",
+        ] {
+            assert_eq!(
+                expand_error(source),
+                "the program expands into more than 1048576 statements, iterations and array elements"
+            );
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use std::fmt;
+use std::str::FromStr;
+
+use num_bigint::BigInt;
+use num_traits::{Num, Signed};
+use rust_ecc::field::FQ;
+use rust_ecc::ntt::{self, NttField};
+
+use crate::ast::Span;
+use crate::circuit::Circuit;
+use crate::r1cs::fq;
+
+/// Prime fields circuits are usually compiled for, each proving backend works over the
+/// scalar field of its own curve
+///
+/// Circuits are field independent until they are lowered, `R1CS::from_circuit` and
+/// the other backends take a `Field` or any other modulus. Arithmetic wraps around at
+/// a different point in every field, lowering rejects constants that already do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Field {
+    /// order `n` of the secp256k1 group, for circuits about ECDSA scalars
+    Secp256k1Order,
+    /// scalar field of bn128, the curve of Groth16 and PLONK verifiers on Ethereum
+    Bn128,
+    /// scalar field of BLS12-381
+    Bls12_381,
+    /// `2^64 - 2^32 + 1`, small enough for native 64-bit arithmetic
+    Goldilocks,
+}
+
+impl Field {
+    pub const ALL: [Field; 4] = [
+        Field::Secp256k1Order,
+        Field::Bn128,
+        Field::Bls12_381,
+        Field::Goldilocks,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Field::Secp256k1Order => "secp256k1",
+            Field::Bn128 => "bn128",
+            Field::Bls12_381 => "bls12_381",
+            Field::Goldilocks => "goldilocks",
+        }
+    }
+
+    pub fn modulus(&self) -> BigInt {
+        match self {
+            // the same value as `n()` of ecdsa-rs's secp256k1 curve
+            Field::Secp256k1Order => BigInt::from_str_radix(
+                "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141",
+                16,
+            )
+            .unwrap(),
+            Field::Bn128 => ntt::bn128_scalar().modulus,
+            Field::Bls12_381 => BigInt::from_str_radix(
+                "73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001",
+                16,
+            )
+            .unwrap(),
+            Field::Goldilocks => ntt::goldilocks().modulus,
+        }
+    }
+
+    /// the named field with this modulus
+    pub fn from_modulus(modulus: &BigInt) -> Option<Field> {
+        Field::ALL
+            .into_iter()
+            .find(|field| field.modulus() == *modulus)
+    }
+
+    /// Roots of unity for the polynomial backends
+    ///
+    /// The secp256k1 order only has a two-adic subgroup of size 64, enough for NTT
+    /// domains of up to 64 points, the backends fall back for larger ones.
+    pub fn ntt(&self) -> Option<NttField> {
+        match self {
+            // 5 is the smallest quadratic non-residue
+            Field::Secp256k1Order => Some(NttField {
+                modulus: self.modulus(),
+                two_adicity: 6,
+                generator: BigInt::from(5u8),
+            }),
+            Field::Bn128 => Some(ntt::bn128_scalar()),
+            Field::Bls12_381 => Some(NttField {
+                modulus: self.modulus(),
+                two_adicity: 32,
+                generator: BigInt::from(7u8),
+            }),
+            Field::Goldilocks => Some(ntt::goldilocks()),
+        }
+    }
+
+    pub fn element(&self, n: BigInt) -> FQ {
+        fq(n, &self.modulus())
+    }
+
+    /// Constants of the circuit that are at least the modulus in absolute value, with
+    /// the span of the gate using them
+    ///
+    /// Lowering stops at the first one, see `Circuit::check_constants`, this lists them
+    /// all, e.g. to check a circuit against several fields at once.
+    pub fn wrapped_constants(&self, circuit: &Circuit) -> Vec<(Span, BigInt)> {
+        let modulus = self.modulus();
+        circuit
+            .constants()
+            .into_iter()
+            .filter(|(_, n)| n.abs() >= modulus)
+            .map(|(span, n)| (span, n.clone()))
+            .collect()
+    }
+}
+
+impl From<Field> for BigInt {
+    fn from(field: Field) -> BigInt {
+        field.modulus()
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// a field name `Field::from_str` does not know
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownField(pub String);
+
+impl fmt::Display for UnknownField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<&str> = Field::ALL.iter().map(|field| field.name()).collect();
+        write!(
+            f,
+            "unknown field `{}`, expected one of {}",
+            self.0,
+            names.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UnknownField {}
+
+impl FromStr for Field {
+    type Err = UnknownField;
+
+    fn from_str(name: &str) -> Result<Field, UnknownField> {
+        Field::ALL
+            .into_iter()
+            .find(|field| field.name() == name)
+            .ok_or_else(|| UnknownField(name.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+    use num_traits::One;
+    use rust_ecc::field::FqElement;
+
+    use super::Field;
+    use crate::error::CompileError;
+    use crate::interpreter::{evaluate, EvalError};
+    use crate::witness::generate_witness;
+    use crate::{flatten, parse, PlonkCircuit, R1CS};
+
+    #[test]
+    fn named_fields() {
+        for field in Field::ALL {
+            assert_eq!(field.name().parse::<Field>(), Ok(field));
+            assert_eq!(Field::from_modulus(&field.modulus()), Some(field));
+            if let Some(ntt) = field.ntt() {
+                // p - 1 = 2^two_adicity * odd and the generator is not a square
+                let p_minus_one: BigInt = &ntt.modulus - 1;
+                assert_eq!(p_minus_one.trailing_zeros(), Some(ntt.two_adicity.into()));
+                let euler = field
+                    .element(ntt.generator.clone())
+                    .pow(FqElement::BigInt(&p_minus_one >> 1));
+                assert_eq!(euler.n, p_minus_one);
+            }
+        }
+        assert_eq!(
+            Field::Goldilocks.modulus(),
+            BigInt::from(18446744069414584321u64)
+        );
+        assert_eq!(Field::Secp256k1Order.ntt().unwrap().two_adicity, 6);
+        assert_eq!(
+            "bn254".parse::<Field>().unwrap_err().to_string(),
+            "unknown field `bn254`, expected one of secp256k1, bn128, bls12_381, goldilocks"
+        );
+    }
+
+    #[test]
+    fn wrap_around_depends_on_the_field() {
+        let source = "private x;\noutput y = x * x * 4294967296;";
+        let circuit = flatten(&parse(source).unwrap()).unwrap();
+        let private = [BigInt::from(1u64 << 48)];
+        let outputs: Vec<BigInt> = Field::ALL
+            .iter()
+            .map(|field| {
+                let r1cs = R1CS::from_circuit(&circuit, *field).unwrap();
+                let witness = generate_witness(&circuit, &r1cs, &[], &private).unwrap();
+                witness[1].n.clone()
+            })
+            .collect();
+        // 2^128 fits in every field but Goldilocks, where 2^96 = -1 and 2^128 = -2^32
+        let expected = BigInt::one() << 128u32;
+        assert_eq!(outputs[..3], [expected.clone(), expected.clone(), expected]);
+        assert_eq!(outputs[3], Field::Goldilocks.modulus() - (1u64 << 32));
+
+        assert!(Field::Goldilocks.wrapped_constants(&circuit).is_empty());
+        let circuit =
+            flatten(&parse("private x;\nassert_eq(x, 18446744069414584322);").unwrap()).unwrap();
+        let wrapped = Field::Goldilocks.wrapped_constants(&circuit);
+        assert_eq!(wrapped.len(), 1);
+        assert_eq!(wrapped[0].1, 18446744069414584322u64.into());
+        assert!(Field::Bn128.wrapped_constants(&circuit).is_empty());
+        assert!(Field::Goldilocks.element(wrapped[0].1.clone()).n.is_one());
+
+        // lowering and the interpreter reject it instead of wrapping silently
+        let err = R1CS::from_circuit(&circuit, Field::Goldilocks).unwrap_err();
+        assert!(
+            matches!(err, CompileError::ConstantOutOfRange { value, .. } if value == wrapped[0].1)
+        );
+        assert!(PlonkCircuit::from_circuit(&circuit, Field::Goldilocks).is_err());
+        let program = parse("private x;\nassert_eq(x, 18446744069414584322);").unwrap();
+        let err = evaluate(&program, &[], &[BigInt::one()], Field::Goldilocks).unwrap_err();
+        assert!(matches!(
+            err,
+            EvalError::Compile(CompileError::ConstantOutOfRange { .. })
+        ));
+        assert!(R1CS::from_circuit(&circuit, Field::Bn128).is_ok());
+    }
+}
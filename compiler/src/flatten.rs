@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+
+use num_bigint::BigInt;
+use num_traits::One;
+
+use crate::ast::{BinaryOp, Expr, ExprKind, Ident, Program, Span, StatementKind};
+use crate::circuit::{Circuit, Gate, GateKind, Hint, Operand, WireInfo, WireKind};
+use crate::error::CompileError;
+use crate::expand::{expand, lookup_table};
+
+/// Flatten a parsed program into a list of addition and multiplication gates
+///
+/// Every nested expression gets its own intermediate wire, subtraction and negation
+/// are rewritten as multiplication by -1 followed by an addition. Division, `inv` and
+/// `sqrt` become a hint gate plus the gates checking its result. A `lookup` becomes a
+/// lookup gate into the circuit's tables, constants it reads are copied onto a wire
+/// first. Functions, loops and arrays are removed by `expand` first.
+pub fn flatten(program: &Program) -> Result<Circuit, CompileError> {
+    let program = expand(program)?;
+    let mut flattener = Flattener {
+        circuit: Circuit::default(),
+        scope: HashMap::new(),
+        tables: HashMap::new(),
+    };
+    for statement in &program.statements {
+        match &statement.kind {
+            StatementKind::Input {
+                visibility, name, ..
+            } => {
+                flattener.define(name, |circuit| {
+                    Operand::Wire(
+                        circuit.new_wire(Some(name.name.clone()), WireKind::Input(*visibility)),
+                    )
+                })?;
+            }
+            StatementKind::Let { name, value, .. } => {
+                let operand = flattener.expr(value)?;
+                flattener.define(name, |circuit| {
+                    // give a fresh intermediate the binding's name so it shows up in the output
+                    if let Operand::Wire(wire) = &operand {
+                        let info = &mut circuit.wires[wire.0];
+                        if info.kind == WireKind::Internal && info.name.is_none() {
+                            info.name = Some(name.name.clone());
+                        }
+                    }
+                    operand
+                })?;
+            }
+            StatementKind::Output { name, value } => {
+                let operand = flattener.expr(value)?;
+                let span = value.span;
+                flattener.define(name, |circuit| {
+                    let name = Some(name.name.clone());
+                    match operand {
+                        // an intermediate becomes the output wire itself
+                        Operand::Wire(wire) if circuit.wires[wire.0].kind == WireKind::Internal => {
+                            circuit.wires[wire.0] = WireInfo {
+                                name,
+                                kind: WireKind::Output,
+                            };
+                            Operand::Wire(wire)
+                        }
+                        // inputs and constants are copied onto a fresh output wire
+                        operand => {
+                            let out = circuit.new_wire(name, WireKind::Output);
+                            circuit.gates.push(Gate {
+                                kind: GateKind::Mul {
+                                    lhs: operand,
+                                    rhs: Operand::Constant(BigInt::one()),
+                                    out,
+                                },
+                                span,
+                            });
+                            Operand::Wire(out)
+                        }
+                    }
+                })?;
+            }
+            StatementKind::AssertEq { lhs, rhs } => {
+                let lhs = flattener.expr(lhs)?;
+                let rhs = flattener.expr(rhs)?;
+                flattener.assert_eq(lhs, rhs, statement.span);
+            }
+            StatementKind::Table { name, value } => {
+                flattener
+                    .tables
+                    .insert(name.name.clone(), flattener.circuit.tables.len());
+                flattener.circuit.tables.push(lookup_table(name, value));
+            }
+            StatementKind::Lookup { table, args } => {
+                let table = flattener.tables[&table.name];
+                let mut wires = Vec::new();
+                for arg in args {
+                    let span = arg.span;
+                    wires.push(match flattener.expr(arg)? {
+                        Operand::Wire(wire) => wire,
+                        constant => {
+                            let one = Operand::Constant(BigInt::one());
+                            let copy = flattener.gate(BinaryOp::Mul, constant, one, span);
+                            copy.wire().expect("gates write a wire")
+                        }
+                    });
+                }
+                flattener.circuit.gates.push(Gate {
+                    kind: GateKind::Lookup { table, args: wires },
+                    span: statement.span,
+                });
+            }
+            StatementKind::Assign { .. }
+            | StatementKind::For { .. }
+            | StatementKind::Return { .. } => {
+                unreachable!("removed by expand")
+            }
+        }
+    }
+    flattener.circuit.check_outputs()?;
+    Ok(flattener.circuit)
+}
+
+struct Flattener {
+    circuit: Circuit,
+    scope: HashMap<String, Operand>,
+    /// index of every table in `circuit.tables`
+    tables: HashMap<String, usize>,
+}
+
+impl Flattener {
+    fn define(
+        &mut self,
+        name: &Ident,
+        value: impl FnOnce(&mut Circuit) -> Operand,
+    ) -> Result<(), CompileError> {
+        if self.scope.contains_key(&name.name) {
+            return Err(CompileError::AlreadyDefined {
+                name: name.name.clone(),
+                span: name.span,
+            });
+        }
+        let operand = value(&mut self.circuit);
+        self.scope.insert(name.name.clone(), operand);
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Result<Operand, CompileError> {
+        match &expr.kind {
+            ExprKind::Constant(n) => Ok(Operand::Constant(n.clone())),
+            ExprKind::Variable(ident) => self.scope.get(&ident.name).cloned().ok_or_else(|| {
+                CompileError::UndefinedVariable {
+                    name: ident.name.clone(),
+                    span: ident.span,
+                }
+            }),
+            ExprKind::Neg(inner) => {
+                let inner = self.expr(inner)?;
+                Ok(self.gate(BinaryOp::Mul, inner, minus_one(), expr.span))
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                let lhs = self.expr(lhs)?;
+                let mut rhs = self.expr(rhs)?;
+                let op = match op {
+                    BinaryOp::Sub => {
+                        rhs = self.gate(BinaryOp::Mul, rhs, minus_one(), expr.span);
+                        BinaryOp::Add
+                    }
+                    BinaryOp::Div => return Ok(self.hint(Hint::Div, vec![lhs, rhs], expr.span)),
+                    op => *op,
+                };
+                Ok(self.gate(op, lhs, rhs, expr.span))
+            }
+            ExprKind::Call { name, args } => {
+                let hint =
+                    Hint::builtin(&name.name).ok_or_else(|| CompileError::UndefinedFunction {
+                        name: name.name.clone(),
+                        span: name.span,
+                    })?;
+                if args.len() != hint.arity() {
+                    return Err(CompileError::ArgumentCount {
+                        name: name.name.clone(),
+                        expected: hint.arity(),
+                        found: args.len(),
+                        span: expr.span,
+                    });
+                }
+                let args = args
+                    .iter()
+                    .map(|arg| self.expr(arg))
+                    .collect::<Result<Vec<Operand>, CompileError>>()?;
+                Ok(self.hint(hint, args, expr.span))
+            }
+            // else + cond * (then - else), with `cond * cond == cond` keeping cond boolean
+            ExprKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                let cond_span = cond.span;
+                let cond = self.expr(cond)?;
+                let squared = self.gate(BinaryOp::Mul, cond.clone(), cond.clone(), cond_span);
+                self.assert_eq(squared, cond.clone(), cond_span);
+                let then_value = self.expr(then_branch)?;
+                let else_value = self.expr(else_branch)?;
+                let negated = self.gate(BinaryOp::Mul, else_value.clone(), minus_one(), expr.span);
+                let difference = self.gate(BinaryOp::Add, then_value, negated, expr.span);
+                let selected = self.gate(BinaryOp::Mul, cond, difference, expr.span);
+                Ok(self.gate(BinaryOp::Add, else_value, selected, expr.span))
+            }
+            ExprKind::Array(_) | ExprKind::Repeat { .. } | ExprKind::Index { .. } => {
+                unreachable!("removed by expand")
+            }
+        }
+    }
+
+    fn gate(&mut self, op: BinaryOp, lhs: Operand, rhs: Operand, span: Span) -> Operand {
+        let out = self.circuit.new_wire(None, WireKind::Internal);
+        let kind = match op {
+            BinaryOp::Add => GateKind::Add { lhs, rhs, out },
+            BinaryOp::Mul => GateKind::Mul { lhs, rhs, out },
+            BinaryOp::Sub | BinaryOp::Div => {
+                unreachable!("subtraction and division are rewritten before emitting a gate")
+            }
+        };
+        self.circuit.gates.push(Gate { kind, span });
+        Operand::Wire(out)
+    }
+
+    /// let the prover compute `hint(args)` and constrain the result
+    fn hint(&mut self, hint: Hint, args: Vec<Operand>, span: Span) -> Operand {
+        let out = self.circuit.new_wire(None, WireKind::Internal);
+        self.circuit.gates.push(Gate {
+            kind: GateKind::Hint {
+                hint,
+                args: args.clone(),
+                out,
+            },
+            span,
+        });
+        let out = Operand::Wire(out);
+        match hint {
+            Hint::Div => {
+                let product = self.gate(BinaryOp::Mul, out.clone(), args[1].clone(), span);
+                self.assert_eq(product, args[0].clone(), span);
+            }
+            Hint::Inverse => {
+                let product = self.gate(BinaryOp::Mul, args[0].clone(), out.clone(), span);
+                self.assert_eq(product, Operand::Constant(BigInt::one()), span);
+            }
+            Hint::Sqrt => {
+                let square = self.gate(BinaryOp::Mul, out.clone(), out.clone(), span);
+                self.assert_eq(square, args[0].clone(), span);
+            }
+        }
+        out
+    }
+
+    fn assert_eq(&mut self, lhs: Operand, rhs: Operand, span: Span) {
+        self.circuit.gates.push(Gate {
+            kind: GateKind::AssertEq { lhs, rhs },
+            span,
+        });
+    }
+}
+
+fn minus_one() -> Operand {
+    Operand::Constant(BigInt::from(-1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::flatten;
+    use crate::ast::Visibility;
+    use crate::parser::parse;
+
+    #[test]
+    fn flatten_nested_expression() {
+        let program =
+            parse("public out;\nprivate x;\nlet y = x * x * x + x + 5;\nassert_eq(y, out);")
+                .unwrap();
+        let circuit = flatten(&program).unwrap();
+        assert_eq!(
+            circuit.to_string(),
+            "w2 = x * x\nw3 = w2 * x\nw4 = w3 + x\ny = w4 + 5\nassert y == out\n"
+        );
+        assert_eq!(circuit.inputs(Visibility::Public).len(), 1);
+        assert_eq!(circuit.inputs(Visibility::Private).len(), 1);
+    }
+
+    #[test]
+    fn subtraction_uses_only_add_and_mul() {
+        let program = parse("private a, b;\nlet d = a - -b;").unwrap();
+        let circuit = flatten(&program).unwrap();
+        assert_eq!(
+            circuit.to_string(),
+            "w2 = b * -1\nw3 = w2 * -1\nd = a + w3\n"
+        );
+    }
+
+    #[test]
+    fn outputs_reuse_intermediates() {
+        let program = parse("private a, b;\noutput p = a * b;\noutput q = a;").unwrap();
+        let circuit = flatten(&program).unwrap();
+        assert_eq!(circuit.to_string(), "p = a * b\nq = a * 1\n");
+        assert_eq!(circuit.outputs().len(), 2);
+    }
+
+    #[test]
+    fn undefined_and_duplicate_names() {
+        let err = flatten(&parse("let a = b;").unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "undefined variable `b`");
+        let err = flatten(&parse("private a;\nlet a = 1;").unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "`a` is already defined");
+    }
+
+    #[test]
+    fn hints_are_checked() {
+        let program = parse("private a, b;\nlet q = a / b;\noutput r = sqrt(inv(q));").unwrap();
+        let circuit = flatten(&program).unwrap();
+        assert_eq!(
+            circuit.to_string(),
+            "q <- div(a, b)\nw3 = q * b\nassert w3 == a\n\
+             w4 <- inv(q)\nw5 = q * w4\nassert w5 == 1\n\
+             r <- sqrt(w4)\nw7 = r * r\nassert w7 == w4\n"
+        );
+        let err = flatten(&parse("private a;\nlet b = sqrt(a, 1);").unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "`sqrt` takes 1 argument(s), found 2");
+        let err = flatten(&parse("let b = cbrt(8);").unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "undefined function `cbrt`");
+    }
+
+    #[test]
+    fn lookups_read_wires() {
+        let source = "table bytes = range(8);\ntable x = xor(4);\nprivate a, b;\nlet c = a + b;\nlookup(x, a, b, c);\nlookup(bytes, 5);\noutput o = c * c;";
+        let circuit = flatten(&parse(source).unwrap()).unwrap();
+        // the constant is copied onto a wire, lookup cells only hold wires
+        assert_eq!(
+            circuit.to_string(),
+            "c = a + b\nlookup x(a, b, c)\nw3 = 5 * 1\nlookup bytes(w3)\no = c * c\n"
+        );
+        assert_eq!(circuit.tables.len(), 2);
+        assert_eq!(circuit.tables[0].entries.len(), 256);
+    }
+
+    #[test]
+    fn conditional_selects_with_gates() {
+        let program = parse("private c, a, b;\noutput m = if c then a else b;").unwrap();
+        let circuit = flatten(&program).unwrap();
+        assert_eq!(
+            circuit.to_string(),
+            "w3 = c * c\nassert w3 == c\nw4 = b * -1\nw5 = a + w4\nw6 = c * w5\nm = b + w6\n"
+        );
+    }
+}
@@ -0,0 +1,8 @@
+// reusable circuits written against the ConstraintSystem trait
+pub mod bits;
+pub mod comparison;
+pub mod field;
+
+pub use bits::{assert_bool, from_bits, range_check, to_bits};
+pub use comparison::{conditional_select, is_equal, is_zero, less_than};
+pub use field::{divide, inverse, sqrt};
@@ -0,0 +1,102 @@
+use num_bigint::BigInt;
+use num_traits::One;
+
+use crate::constraint_system::{ConstraintSystem, Variable};
+use crate::r1cs::{fq, LinearCombination};
+
+/// `b * (b - 1) = 0`, forces `b` to be 0 or 1
+pub fn assert_bool<CS: ConstraintSystem>(cs: &mut CS, b: &LinearCombination) {
+    let b_minus_one = b.sub(&cs.constant(BigInt::one()));
+    cs.enforce(b.clone(), b_minus_one, LinearCombination::default());
+}
+
+/// Decompose `x` into `n` little-endian bits
+///
+/// Costs `n + 1` constraints, one boolean check per bit and one recomposition.
+/// For `2^n` below the field modulus this also proves `x < 2^n`.
+pub fn to_bits<CS: ConstraintSystem>(
+    cs: &mut CS,
+    x: &LinearCombination,
+    n: usize,
+) -> Vec<Variable> {
+    let value = cs.evaluate(x);
+    let p = cs.field_modulus().clone();
+    let bits: Vec<Variable> = (0..n)
+        .map(|i| {
+            let bit = value.as_ref().map(|v| fq((&v.n >> i) & BigInt::one(), &p));
+            let bit = cs.alloc(&format!("bit_{}", i), bit);
+            assert_bool(cs, &cs.lc(bit));
+            bit
+        })
+        .collect();
+    let recomposed = from_bits(cs, &bits);
+    cs.enforce(recomposed, cs.constant(BigInt::one()), x.clone());
+    bits
+}
+
+/// `Σ 2^i * bits[i]`, free since it is a linear combination
+pub fn from_bits<CS: ConstraintSystem>(cs: &CS, bits: &[Variable]) -> LinearCombination {
+    let p = cs.field_modulus();
+    let mut lc = LinearCombination::default();
+    for (i, bit) in bits.iter().enumerate() {
+        lc.add_term(bit.0, fq(BigInt::one() << i, p));
+    }
+    lc
+}
+
+/// Prove `0 <= x < 2^nbits`
+pub fn range_check<CS: ConstraintSystem>(cs: &mut CS, x: &LinearCombination, nbits: usize) {
+    assert!(
+        (BigInt::one() << nbits) < *cs.field_modulus(),
+        "a {} bit range check wraps around the field",
+        nbits
+    );
+    to_bits(cs, x, nbits);
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use super::{assert_bool, range_check, to_bits};
+    use crate::constraint_system::{ConstraintSystem, R1CSBuilder, WitnessBuilder};
+    use crate::r1cs::fq;
+
+    #[test]
+    fn decompose_and_recompose() {
+        let p = BigInt::from(1009);
+        let mut cs = WitnessBuilder::new(p.clone()).unwrap();
+        let x = cs.alloc_input("x", Some(fq(13.into(), &p)));
+        let x = cs.lc(x);
+        let bits = to_bits(&mut cs, &x, 4);
+        let values: Vec<BigInt> = bits.iter().map(|b| cs.value(*b).unwrap().n).collect();
+        assert_eq!(values, [1, 0, 1, 1].map(BigInt::from));
+        assert!(cs.unsatisfied.is_empty());
+
+        let mut builder = R1CSBuilder::new(p).unwrap();
+        let x = builder.alloc_input("x", None);
+        let x = builder.lc(x);
+        to_bits(&mut builder, &x, 4);
+        let r1cs = builder.into_r1cs();
+        assert_eq!(r1cs.num_constraints(), 5);
+        assert!(r1cs.is_satisfied(&cs.into_witness().unwrap()));
+    }
+
+    #[test]
+    fn range_check_rejects_large_values() {
+        let p = BigInt::from(1009);
+        for (x, ok) in [(0, true), (255, true), (256, false), (-1, false)] {
+            let mut cs = WitnessBuilder::new(p.clone()).unwrap();
+            let x = cs.alloc_input("x", Some(fq(x.into(), &p)));
+            let x = cs.lc(x);
+            range_check(&mut cs, &x, 8);
+            assert_eq!(cs.unsatisfied.is_empty(), ok);
+        }
+
+        let mut cs = WitnessBuilder::new(p.clone()).unwrap();
+        let b = cs.alloc_input("b", Some(fq(2.into(), &p)));
+        let b = cs.lc(b);
+        assert_bool(&mut cs, &b);
+        assert_eq!(cs.unsatisfied, vec![0]);
+    }
+}
@@ -0,0 +1,136 @@
+use num_bigint::BigInt;
+use num_traits::{One, Zero};
+use rust_ecc::field::FqElement;
+use rust_ecc::math::prime_field_inv;
+
+use crate::constraint_system::{ConstraintSystem, Variable};
+use crate::gadgets::bits::to_bits;
+use crate::r1cs::{fq, LinearCombination};
+
+/// 1 if `x` is zero and 0 otherwise
+///
+/// The prover supplies `inv = x^-1` (or 0 when `x = 0`) as a hint, then
+/// `x * inv = 1 - out` and `x * out = 0` pin `out` down.
+pub fn is_zero<CS: ConstraintSystem>(cs: &mut CS, x: &LinearCombination) -> Variable {
+    let p = cs.field_modulus().clone();
+    let value = cs.evaluate(x);
+    let inv = value
+        .as_ref()
+        .map(|v| fq(prime_field_inv(v.n.clone(), p.clone()), &p));
+    let out = value.map(|v| fq(BigInt::from(v.n.is_zero() as u8), &p));
+    let inv = cs.alloc("is_zero_inv", inv);
+    let out = cs.alloc("is_zero", out);
+    let one_minus_out = cs.constant(BigInt::one()).sub(&cs.lc(out));
+    cs.enforce(x.clone(), cs.lc(inv), one_minus_out);
+    cs.enforce(x.clone(), cs.lc(out), LinearCombination::default());
+    out
+}
+
+/// 1 if `a == b` and 0 otherwise
+pub fn is_equal<CS: ConstraintSystem>(
+    cs: &mut CS,
+    a: &LinearCombination,
+    b: &LinearCombination,
+) -> Variable {
+    is_zero(cs, &a.sub(b))
+}
+
+/// 1 if `a < b` and 0 otherwise, for `a` and `b` already known to fit in `nbits` bits
+///
+/// `a - b + 2^nbits` is decomposed into `nbits + 1` bits, its top bit is set exactly
+/// when `a >= b`.
+pub fn less_than<CS: ConstraintSystem>(
+    cs: &mut CS,
+    a: &LinearCombination,
+    b: &LinearCombination,
+    nbits: usize,
+) -> Variable {
+    assert!(
+        (BigInt::one() << (nbits + 1)) < *cs.field_modulus(),
+        "a {} bit comparison wraps around the field",
+        nbits
+    );
+    let offset = cs.constant(BigInt::one() << nbits);
+    let bits = to_bits(cs, &a.sub(b).add(&offset), nbits + 1);
+    let top = cs.lc(bits[nbits]);
+    let lt = cs.constant(BigInt::one()).sub(&top);
+    let out = cs.alloc("less_than", cs.evaluate(&lt));
+    cs.enforce(lt, cs.constant(BigInt::one()), cs.lc(out));
+    out
+}
+
+/// `a` if `cond` is 1 and `b` if it is 0, `cond` must already be boolean
+///
+/// One constraint: `cond * (a - b) = out - b`.
+pub fn conditional_select<CS: ConstraintSystem>(
+    cs: &mut CS,
+    cond: &LinearCombination,
+    a: &LinearCombination,
+    b: &LinearCombination,
+) -> Variable {
+    let value = match (cs.evaluate(cond), cs.evaluate(a), cs.evaluate(b)) {
+        (Some(cond), Some(a), Some(b)) => Some(b.add(FqElement::FQ(
+            cond.mul(FqElement::FQ(a.sub(FqElement::FQ(b.clone())))),
+        ))),
+        _ => None,
+    };
+    let out = cs.alloc("select", value);
+    let out_minus_b = cs.lc(out).sub(b);
+    cs.enforce(cond.clone(), a.sub(b), out_minus_b);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use super::{conditional_select, is_equal, is_zero, less_than};
+    use crate::constraint_system::{ConstraintSystem, R1CSBuilder, WitnessBuilder};
+    use crate::r1cs::fq;
+
+    #[test]
+    fn zero_and_equality() {
+        let p = BigInt::from(1009);
+        for (x, expected) in [(0, 1), (5, 0), (-3, 0)] {
+            let mut cs = WitnessBuilder::new(p.clone()).unwrap();
+            let x = cs.alloc_input("x", Some(fq(x.into(), &p)));
+            let x = cs.lc(x);
+            let out = is_zero(&mut cs, &x);
+            assert_eq!(cs.value(out).unwrap().n, BigInt::from(expected));
+            assert!(cs.unsatisfied.is_empty());
+        }
+
+        let mut cs = WitnessBuilder::new(p.clone()).unwrap();
+        let a = cs.alloc_input("a", Some(fq(7.into(), &p)));
+        let b = cs.alloc_input("b", Some(fq(7.into(), &p)));
+        let (a, b) = (cs.lc(a), cs.lc(b));
+        let eq = is_equal(&mut cs, &a, &b);
+        assert_eq!(cs.value(eq).unwrap().n, BigInt::from(1));
+    }
+
+    #[test]
+    fn comparison_and_select() {
+        let p = BigInt::from(1009);
+        for (a, b, expected) in [(3, 9, 1), (9, 3, 0), (4, 4, 0), (0, 15, 1)] {
+            let mut cs = WitnessBuilder::new(p.clone()).unwrap();
+            let a_var = cs.alloc_input("a", Some(fq(a.into(), &p)));
+            let b_var = cs.alloc_input("b", Some(fq(b.into(), &p)));
+            let (a_lc, b_lc) = (cs.lc(a_var), cs.lc(b_var));
+            let lt = less_than(&mut cs, &a_lc, &b_lc, 4);
+            assert_eq!(cs.value(lt).unwrap().n, BigInt::from(expected));
+            // min(a, b)
+            let lt = cs.lc(lt);
+            let min = conditional_select(&mut cs, &lt, &a_lc, &b_lc);
+            assert_eq!(cs.value(min).unwrap().n, BigInt::from(a.min(b)));
+            assert!(cs.unsatisfied.is_empty());
+        }
+
+        let mut builder = R1CSBuilder::new(p).unwrap();
+        let a = builder.alloc_input("a", None);
+        let b = builder.alloc_input("b", None);
+        let (a, b) = (builder.lc(a), builder.lc(b));
+        less_than(&mut builder, &a, &b, 4);
+        // 5 boolean checks, the recomposition and the output
+        assert_eq!(builder.into_r1cs().num_constraints(), 7);
+    }
+}
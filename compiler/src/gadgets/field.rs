@@ -0,0 +1,85 @@
+use num_bigint::BigInt;
+use num_traits::{One, Zero};
+
+use crate::circuit::Hint;
+use crate::constraint_system::{ConstraintSystem, Variable};
+use crate::r1cs::{fq, LinearCombination};
+
+/// `a / b`, computed as a hint and checked with `out * b = a`
+pub fn divide<CS: ConstraintSystem>(
+    cs: &mut CS,
+    a: &LinearCombination,
+    b: &LinearCombination,
+) -> Variable {
+    let out = advice(cs, Hint::Div, &[a, b]);
+    cs.enforce(cs.lc(out), b.clone(), a.clone());
+    out
+}
+
+/// `x^-1`, checked with `x * out = 1`
+pub fn inverse<CS: ConstraintSystem>(cs: &mut CS, x: &LinearCombination) -> Variable {
+    let out = advice(cs, Hint::Inverse, &[x]);
+    cs.enforce(x.clone(), cs.lc(out), cs.constant(BigInt::one()));
+    out
+}
+
+/// a square root of `x`, checked with `out * out = x`
+///
+/// Either root passes the check, the hint only assigns the smaller one, so the result
+/// is not determined by `x`.
+pub fn sqrt<CS: ConstraintSystem>(cs: &mut CS, x: &LinearCombination) -> Variable {
+    let out = advice(cs, Hint::Sqrt, &[x]);
+    cs.enforce(cs.lc(out), cs.lc(out), x.clone());
+    out
+}
+
+/// allocate the hint result, a hint without an answer is assigned zero so the checking
+/// constraint shows up as unsatisfied
+fn advice<CS: ConstraintSystem>(cs: &mut CS, hint: Hint, args: &[&LinearCombination]) -> Variable {
+    let p = cs.field_modulus().clone();
+    let args: Option<Vec<_>> = args.iter().map(|arg| cs.evaluate(arg)).collect();
+    let value = args.map(|args| {
+        hint.compute(&args, &p)
+            .unwrap_or_else(|| fq(BigInt::zero(), &p))
+    });
+    cs.alloc(hint.name(), value)
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use super::{divide, inverse, sqrt};
+    use crate::constraint_system::{ConstraintSystem, WitnessBuilder};
+    use crate::r1cs::fq;
+
+    #[test]
+    fn checked_hints() {
+        let p = BigInt::from(1009);
+        let mut cs = WitnessBuilder::new(p.clone()).unwrap();
+        let a = cs.alloc_input("a", Some(fq(12.into(), &p)));
+        let b = cs.alloc_input("b", Some(fq(3.into(), &p)));
+        let (a, b) = (cs.lc(a), cs.lc(b));
+        let q = divide(&mut cs, &a, &b);
+        assert_eq!(cs.value(q).unwrap().n, BigInt::from(4));
+        let inv = inverse(&mut cs, &b);
+        assert_eq!(cs.value(inv).unwrap().n, BigInt::from(673));
+        let sixteen = cs.constant(16.into());
+        let root = sqrt(&mut cs, &sixteen);
+        assert_eq!(cs.value(root).unwrap().n, BigInt::from(4));
+        assert!(cs.unsatisfied.is_empty());
+    }
+
+    #[test]
+    fn failing_hints_break_their_constraint() {
+        let p = BigInt::from(1009);
+        let mut cs = WitnessBuilder::new(p.clone()).unwrap();
+        let zero = cs.alloc_input("zero", Some(fq(0.into(), &p)));
+        let zero = cs.lc(zero);
+        inverse(&mut cs, &zero);
+        // 11 is not a square mod 1009
+        let eleven = cs.constant(11.into());
+        sqrt(&mut cs, &eleven);
+        assert_eq!(cs.unsatisfied, vec![0, 1]);
+    }
+}
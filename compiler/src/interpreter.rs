@@ -0,0 +1,401 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_traits::{One, Signed, Zero};
+use rust_ecc::field::{FqElement, FQ};
+
+use crate::ast::{BinaryOp, Expr, ExprKind, Program, Span, StatementKind, Visibility};
+use crate::circuit::Hint;
+use crate::error::CompileError;
+use crate::expand::{expand, lookup_table};
+use crate::plonk::LookupTable;
+use crate::r1cs::{check_modulus, fq};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EvalError {
+    /// the program does not compile
+    Compile(CompileError),
+    /// the number of provided inputs does not match the declarations
+    InputCount {
+        visibility: Visibility,
+        expected: usize,
+        found: usize,
+    },
+    /// both sides of an `assert_eq` evaluated to different values
+    AssertionFailed {
+        lhs: BigInt,
+        rhs: BigInt,
+        span: Span,
+    },
+    /// a division by zero, the inverse of zero or the square root of a non-residue
+    HintFailed { hint: Hint, span: Span },
+    /// the condition of an `if` evaluated to something other than 0 or 1
+    NonBooleanCondition { value: BigInt, span: Span },
+    /// the values of a `lookup` are not an entry of its table
+    LookupFailed { table: String, span: Span },
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalError::Compile(err) => write!(f, "{}", err),
+            EvalError::InputCount {
+                visibility,
+                expected,
+                found,
+            } => write!(
+                f,
+                "expected {} {} input(s), found {}",
+                expected,
+                visibility.keyword(),
+                found
+            ),
+            EvalError::AssertionFailed { lhs, rhs, .. } => {
+                write!(f, "assertion failed: {} != {}", lhs, rhs)
+            }
+            EvalError::HintFailed { hint, .. } => {
+                write!(f, "`{}` has no value for its arguments", hint.name())
+            }
+            EvalError::NonBooleanCondition { value, .. } => {
+                write!(f, "condition is {}, expected 0 or 1", value)
+            }
+            EvalError::LookupFailed { table, .. } => {
+                write!(f, "the looked up values are not an entry of `{}`", table)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EvalError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EvalError::Compile(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Run a program directly over the prime field without building any constraints
+///
+/// Inputs are given in declaration order, the outputs are returned as (name, value)
+/// pairs in declaration order.
+pub fn evaluate(
+    program: &Program,
+    public: &[BigInt],
+    private: &[BigInt],
+    field_modulus: impl Into<BigInt>,
+) -> Result<Vec<(String, FQ)>, EvalError> {
+    let field_modulus = &field_modulus.into();
+    check_modulus(field_modulus).map_err(EvalError::Compile)?;
+    let program = expand(program).map_err(EvalError::Compile)?;
+    let mut scope: HashMap<String, FQ> = HashMap::new();
+    for (visibility, values) in [(Visibility::Public, public), (Visibility::Private, private)] {
+        let inputs = program.inputs(visibility);
+        if inputs.len() != values.len() {
+            return Err(EvalError::InputCount {
+                visibility,
+                expected: inputs.len(),
+                found: values.len(),
+            });
+        }
+        for (name, value) in inputs.iter().zip(values) {
+            scope.insert(name.name.clone(), fq(value.clone(), field_modulus));
+        }
+    }
+
+    let mut tables: HashMap<String, LookupTable> = HashMap::new();
+    // entries are range checked at the first lookup, like the lowering does
+    let mut checked: HashSet<String> = HashSet::new();
+    let mut outputs: Vec<(String, FQ)> = Vec::new();
+    for statement in &program.statements {
+        match &statement.kind {
+            StatementKind::Input { .. } => {}
+            StatementKind::Let { name, value, .. } => {
+                let value = eval_expr(value, &scope, field_modulus)?;
+                scope.insert(name.name.clone(), value);
+            }
+            StatementKind::Output { name, value } => {
+                let value = eval_expr(value, &scope, field_modulus)?;
+                scope.insert(name.name.clone(), value.clone());
+                outputs.push((name.name.clone(), value));
+            }
+            StatementKind::AssertEq { lhs, rhs } => {
+                let lhs = eval_expr(lhs, &scope, field_modulus)?;
+                let rhs = eval_expr(rhs, &scope, field_modulus)?;
+                if lhs != rhs {
+                    return Err(EvalError::AssertionFailed {
+                        lhs: lhs.n,
+                        rhs: rhs.n,
+                        span: statement.span,
+                    });
+                }
+            }
+            StatementKind::Table { name, value } => {
+                tables.insert(name.name.clone(), lookup_table(name, value));
+            }
+            StatementKind::Lookup { table, args } => {
+                let values = args
+                    .iter()
+                    .map(|arg| eval_expr(arg, &scope, field_modulus))
+                    .collect::<Result<Vec<FQ>, EvalError>>()?;
+                let declared = &tables[&table.name];
+                if checked.insert(table.name.clone()) {
+                    let mut numbers = declared.entries.iter().flatten();
+                    if let Some(n) = numbers.find(|n| n.abs() >= *field_modulus) {
+                        return Err(EvalError::Compile(CompileError::ConstantOutOfRange {
+                            value: n.clone(),
+                            field_modulus: field_modulus.clone(),
+                            span: statement.span,
+                        }));
+                    }
+                }
+                if !declared.contains(&values) {
+                    return Err(EvalError::LookupFailed {
+                        table: table.name.clone(),
+                        span: statement.span,
+                    });
+                }
+            }
+            StatementKind::Assign { .. }
+            | StatementKind::For { .. }
+            | StatementKind::Return { .. } => {
+                unreachable!("removed by expand")
+            }
+        }
+    }
+    Ok(outputs)
+}
+
+fn eval_expr(
+    expr: &Expr,
+    scope: &HashMap<String, FQ>,
+    field_modulus: &BigInt,
+) -> Result<FQ, EvalError> {
+    match &expr.kind {
+        // rejected like the lowering does
+        ExprKind::Constant(n) if n.abs() >= *field_modulus => {
+            Err(EvalError::Compile(CompileError::ConstantOutOfRange {
+                value: n.clone(),
+                field_modulus: field_modulus.clone(),
+                span: expr.span,
+            }))
+        }
+        ExprKind::Constant(n) => Ok(fq(n.clone(), field_modulus)),
+        ExprKind::Variable(ident) => scope.get(&ident.name).cloned().ok_or_else(|| {
+            EvalError::Compile(CompileError::UndefinedVariable {
+                name: ident.name.clone(),
+                span: ident.span,
+            })
+        }),
+        ExprKind::Neg(inner) => {
+            let inner = eval_expr(inner, scope, field_modulus)?;
+            Ok(fq(BigInt::from(0), field_modulus).sub(FqElement::FQ(inner)))
+        }
+        ExprKind::Binary { op, lhs, rhs } => {
+            let lhs = eval_expr(lhs, scope, field_modulus)?;
+            let rhs = eval_expr(rhs, scope, field_modulus)?;
+            Ok(match op {
+                BinaryOp::Add => lhs.add(FqElement::FQ(rhs)),
+                BinaryOp::Sub => lhs.sub(FqElement::FQ(rhs)),
+                BinaryOp::Mul => lhs.mul(FqElement::FQ(rhs)),
+                BinaryOp::Div => eval_hint(Hint::Div, &[lhs, rhs], expr.span, field_modulus)?,
+            })
+        }
+        ExprKind::Call { name, args } => {
+            let hint = Hint::builtin(&name.name).ok_or_else(|| {
+                EvalError::Compile(CompileError::UndefinedFunction {
+                    name: name.name.clone(),
+                    span: name.span,
+                })
+            })?;
+            if args.len() != hint.arity() {
+                return Err(EvalError::Compile(CompileError::ArgumentCount {
+                    name: name.name.clone(),
+                    expected: hint.arity(),
+                    found: args.len(),
+                    span: expr.span,
+                }));
+            }
+            let args = args
+                .iter()
+                .map(|arg| eval_expr(arg, scope, field_modulus))
+                .collect::<Result<Vec<FQ>, EvalError>>()?;
+            eval_hint(hint, &args, expr.span, field_modulus)
+        }
+        ExprKind::If {
+            cond,
+            then_branch,
+            else_branch,
+        } => {
+            // both branches are part of the circuit, a hint failing in the branch not
+            // taken leaves it unsatisfiable all the same
+            let value = eval_expr(cond, scope, field_modulus)?;
+            let then_value = eval_expr(then_branch, scope, field_modulus)?;
+            let else_value = eval_expr(else_branch, scope, field_modulus)?;
+            if value.n.is_one() {
+                Ok(then_value)
+            } else if value.n.is_zero() {
+                Ok(else_value)
+            } else {
+                Err(EvalError::NonBooleanCondition {
+                    value: value.n,
+                    span: cond.span,
+                })
+            }
+        }
+        ExprKind::Array(_) | ExprKind::Repeat { .. } | ExprKind::Index { .. } => {
+            unreachable!("removed by expand")
+        }
+    }
+}
+
+fn eval_hint(hint: Hint, args: &[FQ], span: Span, field_modulus: &BigInt) -> Result<FQ, EvalError> {
+    hint.compute(args, field_modulus)
+        .ok_or(EvalError::HintFailed { hint, span })
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use rust_ecc::field::{FqElement, FQ};
+
+    use super::{evaluate, EvalError};
+    use crate::circuit::Hint;
+    use crate::error::CompileError;
+    use crate::test_utils::ints;
+    use crate::{flatten, generate_witness, parse, WitnessError, R1CS};
+
+    #[test]
+    fn evaluate_outputs() {
+        let program = parse("private x;\noutput y = x * x - 3;\noutput z = y * -1;").unwrap();
+        let p = BigInt::from(101);
+        let outputs = evaluate(&program, &[], &ints(&[5]), p.clone()).unwrap();
+        let values: Vec<(&str, BigInt)> = outputs
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.n.clone()))
+            .collect();
+        assert_eq!(values, vec![("y", 22.into()), ("z", 79.into())]);
+
+        let program = parse("private a;\nassert_eq(a, 2);").unwrap();
+        let err = evaluate(&program, &[], &ints(&[3]), p.clone()).unwrap_err();
+        assert_eq!(err.to_string(), "assertion failed: 3 != 2");
+        assert!(
+            matches!(err, EvalError::AssertionFailed { lhs, rhs, .. } if lhs == 3.into() && rhs == 2.into())
+        );
+
+        // compile errors stay reachable through `source`
+        let err = evaluate(&parse("let a = b;").unwrap(), &[], &[], p.clone()).unwrap_err();
+        let source = std::error::Error::source(&err).unwrap();
+        assert_eq!(source.to_string(), "undefined variable `b`");
+    }
+
+    #[test]
+    fn lookups() {
+        let source = "table squares = [[0, 0], [1, 1], [2, 4], [3, 9]];\nprivate x, y;\nlookup(squares, x, y);\noutput z = y + 1;";
+        let program = parse(source).unwrap();
+        let p = BigInt::from(101);
+        let outputs = evaluate(&program, &[], &ints(&[3, 9]), p.clone()).unwrap();
+        assert_eq!(outputs[0].1.n, BigInt::from(10));
+        let err = evaluate(&program, &[], &ints(&[3, 8]), p.clone()).unwrap_err();
+        assert!(matches!(err, EvalError::LookupFailed { table, .. } if table == "squares"));
+        let program = parse("table big = [1000];\nprivate x;\nlookup(big, x);").unwrap();
+        let err = evaluate(&program, &[], &ints(&[1000]), p.clone()).unwrap_err();
+        assert!(matches!(
+            err,
+            EvalError::Compile(CompileError::ConstantOutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn matches_r1cs_witness() {
+        let source = "public a;\nprivate b, c;\nlet t = a * b - c;\noutput u = t * t + a;\noutput v = 7 - u;";
+        let program = parse(source).unwrap();
+        let circuit = flatten(&program).unwrap();
+        let p = BigInt::from(1009);
+        let r1cs = R1CS::from_circuit(&circuit, p.clone()).unwrap();
+        for (a, b, c) in [(1, 2, 3), (5, 0, 9), (-4, 8, 1000)] {
+            let (public, private) = (ints(&[a]), ints(&[b, c]));
+            let outputs = evaluate(&program, &public, &private, p.clone()).unwrap();
+            let witness = generate_witness(&circuit, &r1cs, &public, &private).unwrap();
+            for (wire, variable) in circuit.outputs().iter().zip(r1cs.output_variables()) {
+                let name = circuit.wire_name(*wire);
+                let (_, expected) = outputs.iter().find(|(n, _)| *n == name).unwrap();
+                assert_eq!(&witness[variable], expected);
+            }
+        }
+    }
+
+    #[test]
+    fn hints_match_r1cs_witness() {
+        let source = "private a, b;\noutput q = a / b;\noutput r = sqrt(a * a) + inv(b);";
+        let program = parse(source).unwrap();
+        let circuit = flatten(&program).unwrap();
+        let p = BigInt::from(1009);
+        let r1cs = R1CS::from_circuit(&circuit, p.clone()).unwrap();
+        let outputs = evaluate(&program, &[], &ints(&[6, 4]), p.clone()).unwrap();
+        let witness = generate_witness(&circuit, &r1cs, &[], &ints(&[6, 4])).unwrap();
+        let expected: Vec<FQ> = outputs.into_iter().map(|(_, value)| value).collect();
+        assert_eq!(witness[r1cs.output_variables()], expected[..]);
+        // q * 4 == 6 and (6 + 4^-1) * 4 == 25
+        let check = |v: &FQ| v.mul(FqElement::BigInt(4.into())).n;
+        assert_eq!(
+            (check(&expected[0]), check(&expected[1])),
+            (6.into(), 25.into())
+        );
+
+        let err = evaluate(&program, &[], &ints(&[6, 0]), p.clone()).unwrap_err();
+        assert!(matches!(
+            err,
+            EvalError::HintFailed {
+                hint: Hint::Div,
+                ..
+            }
+        ));
+        let err = generate_witness(&circuit, &r1cs, &[], &ints(&[6, 0])).unwrap_err();
+        assert!(matches!(err, WitnessError::HintFailed { gate: 0, .. }));
+    }
+
+    #[test]
+    fn conditional_matches_r1cs_witness() {
+        let source = "public c;\nprivate a, b;\noutput m = if c then a * b else a - b;";
+        let program = parse(source).unwrap();
+        let circuit = flatten(&program).unwrap();
+        let p = BigInt::from(1009);
+        let r1cs = R1CS::from_circuit(&circuit, p.clone()).unwrap();
+        for (c, expected) in [(1, 12), (0, 1)] {
+            let (public, private) = (ints(&[c]), ints(&[4, 3]));
+            let outputs = evaluate(&program, &public, &private, p.clone()).unwrap();
+            assert_eq!(outputs[0].1.n, BigInt::from(expected));
+            let witness = generate_witness(&circuit, &r1cs, &public, &private).unwrap();
+            assert_eq!(witness[r1cs.output_variables().start], outputs[0].1);
+        }
+
+        let err = evaluate(&program, &ints(&[2]), &ints(&[4, 3]), p.clone()).unwrap_err();
+        assert!(matches!(err, EvalError::NonBooleanCondition { value, .. } if value == 2.into()));
+        assert!(generate_witness(&circuit, &r1cs, &ints(&[2]), &ints(&[4, 3])).is_err());
+
+        // the branch not taken still has to have a witness
+        let program = parse("private c, a, b;\noutput m = if c then a / b else a;").unwrap();
+        let circuit = flatten(&program).unwrap();
+        let r1cs = R1CS::from_circuit(&circuit, p.clone()).unwrap();
+        let inputs = ints(&[0, 4, 0]);
+        let err = evaluate(&program, &[], &inputs, p.clone()).unwrap_err();
+        assert!(matches!(
+            err,
+            EvalError::HintFailed {
+                hint: Hint::Div,
+                ..
+            }
+        ));
+        let err = generate_witness(&circuit, &r1cs, &[], &inputs).unwrap_err();
+        assert!(matches!(
+            err,
+            WitnessError::HintFailed {
+                hint: Hint::Div,
+                ..
+            }
+        ));
+    }
+}
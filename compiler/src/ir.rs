@@ -0,0 +1,712 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use num_bigint::BigInt;
+use serde::{Deserialize, Serialize};
+
+use crate::ast::{Span, Visibility};
+use crate::circuit::{Circuit, Gate, GateKind, Hint, Operand, Wire, WireInfo, WireKind};
+use crate::field::Field;
+use crate::plonk::LookupTable;
+use crate::r1cs::{fq, LinearCombination, R1CS};
+
+/// Schema version written to and required from every document
+pub const VERSION: u32 = 1;
+
+/// A compiled circuit as stored on disk, see `to_json` for the format
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompiledCircuit {
+    pub field_modulus: BigInt,
+    pub circuit: Circuit,
+    /// lowered constraint system, left out when only the gate list is shipped
+    pub r1cs: Option<R1CS>,
+    /// free-form key/value pairs, e.g. the source file name
+    pub metadata: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IrError {
+    /// not JSON or not the shape of the schema
+    Json(String),
+    UnsupportedVersion(u64),
+    /// well-formed JSON describing an inconsistent circuit
+    Invalid(String),
+}
+
+impl fmt::Display for IrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IrError::Json(message) => write!(f, "{}", message),
+            IrError::UnsupportedVersion(v) => {
+                write!(f, "unsupported schema version {}, expected {}", v, VERSION)
+            }
+            IrError::Invalid(message) => write!(f, "invalid circuit: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for IrError {}
+
+impl CompiledCircuit {
+    /// bundle a circuit with its R1CS, recording the compiler version and the name of
+    /// the field, if it has one, as metadata
+    pub fn new(circuit: Circuit, r1cs: R1CS) -> CompiledCircuit {
+        let mut metadata = BTreeMap::new();
+        metadata.insert(
+            "compiler".to_string(),
+            format!("compiler {}", env!("CARGO_PKG_VERSION")),
+        );
+        if let Some(field) = Field::from_modulus(&r1cs.field_modulus) {
+            metadata.insert("field".to_string(), field.name().to_string());
+        }
+        CompiledCircuit {
+            field_modulus: r1cs.field_modulus.clone(),
+            circuit,
+            r1cs: Some(r1cs),
+            metadata,
+        }
+    }
+
+    /// Pretty-printed JSON document
+    ///
+    /// ```text
+    /// {
+    ///   "version": 1,
+    ///   "field": "1009",
+    ///   "metadata": { "compiler": "compiler 0.1.0" },
+    ///   "wires": [{ "name": "x", "kind": "private_input" }, { "kind": "internal" }, ...],
+    ///   "public_inputs": [], "private_inputs": [0], "outputs": [2],
+    ///   "gates": [{ "op": "mul", "args": [0, "5"], "out": 1, "span": [8, 13] }, ...],
+    ///   "r1cs": {
+    ///     "num_variables": 3, "num_outputs": 1, "num_public": 0, "num_private": 1,
+    ///     "constraints": [{ "a": [[2, "1"]], "b": [[0, "5"]], "c": [[1, "1"]], "gate": 0 }],
+    ///     "wire_variables": [2, null, 1]
+    ///   }
+    /// }
+    /// ```
+    ///
+    /// Numbers that can exceed 64 bits, the field modulus, constants and coefficients,
+    /// are decimal strings. Wire kinds are `public_input`, `private_input`, `output`
+    /// and `internal`, the input and output lists repeat them in declaration order.
+    /// Gate ops are `add`, `mul`, `assert_eq` (no `out`) and the hints `div`, `inv` and
+    /// `sqrt`, an argument is a wire index or a constant string. A `lookup` gate (no
+    /// `out`, only wire arguments) names its table by index into `"tables"`, a list of
+    /// `{ "name": "bytes", "entries": [["0"], ["1"], ...] }` left out when empty. Spans
+    /// are byte ranges into the source.
+    pub fn to_json(&self) -> String {
+        let document = Document {
+            version: VERSION,
+            field: self.field_modulus.to_string(),
+            metadata: self.metadata.clone(),
+            wires: self
+                .circuit
+                .wires
+                .iter()
+                .map(|info| JsonWire {
+                    name: info.name.clone(),
+                    kind: match info.kind {
+                        WireKind::Input(Visibility::Public) => JsonWireKind::PublicInput,
+                        WireKind::Input(Visibility::Private) => JsonWireKind::PrivateInput,
+                        WireKind::Output => JsonWireKind::Output,
+                        WireKind::Internal => JsonWireKind::Internal,
+                    },
+                })
+                .collect(),
+            public_inputs: indices(&self.circuit.inputs(Visibility::Public)),
+            private_inputs: indices(&self.circuit.inputs(Visibility::Private)),
+            outputs: indices(&self.circuit.outputs()),
+            gates: self.circuit.gates.iter().map(json_gate).collect(),
+            tables: self
+                .circuit
+                .tables
+                .iter()
+                .map(|table| JsonTable {
+                    name: table.name.clone(),
+                    entries: table
+                        .entries
+                        .iter()
+                        .map(|entry| entry.iter().map(|n| n.to_string()).collect())
+                        .collect(),
+                })
+                .collect(),
+            r1cs: self.r1cs.as_ref().map(|r1cs| JsonR1CS {
+                num_variables: r1cs.num_variables,
+                num_outputs: r1cs.num_outputs,
+                num_public: r1cs.num_public,
+                num_private: r1cs.num_private,
+                constraints: (0..r1cs.num_constraints())
+                    .map(|i| JsonConstraint {
+                        a: json_lc(&r1cs.a[i]),
+                        b: json_lc(&r1cs.b[i]),
+                        c: json_lc(&r1cs.c[i]),
+                        gate: r1cs.constraint_gates[i],
+                    })
+                    .collect(),
+                wire_variables: r1cs.wire_variables.clone(),
+            }),
+        };
+        serde_json::to_string_pretty(&document).expect("documents are always serializable")
+    }
+
+    /// Load a document written by `to_json`, checking every index it contains and that
+    /// gates only read wires earlier gates wrote
+    pub fn from_json(json: &str) -> Result<CompiledCircuit, IrError> {
+        let value: serde_json::Value =
+            serde_json::from_str(json).map_err(|e| IrError::Json(e.to_string()))?;
+        match value.get("version").and_then(|v| v.as_u64()) {
+            Some(v) if v == VERSION as u64 => {}
+            Some(v) => return Err(IrError::UnsupportedVersion(v)),
+            None => return Err(IrError::Json("missing field `version`".to_string())),
+        }
+        let document: Document =
+            serde_json::from_value(value).map_err(|e| IrError::Json(e.to_string()))?;
+
+        let field_modulus = number(&document.field)?;
+        if field_modulus <= BigInt::from(1) {
+            return Err(invalid("the field modulus must be greater than one"));
+        }
+        let circuit = Circuit {
+            wires: document
+                .wires
+                .into_iter()
+                .map(|wire| WireInfo {
+                    name: wire.name,
+                    kind: match wire.kind {
+                        JsonWireKind::PublicInput => WireKind::Input(Visibility::Public),
+                        JsonWireKind::PrivateInput => WireKind::Input(Visibility::Private),
+                        JsonWireKind::Output => WireKind::Output,
+                        JsonWireKind::Internal => WireKind::Internal,
+                    },
+                })
+                .collect(),
+            gates: Vec::new(),
+            tables: document
+                .tables
+                .iter()
+                .map(circuit_table)
+                .collect::<Result<Vec<LookupTable>, IrError>>()?,
+        };
+        let gates = document
+            .gates
+            .iter()
+            .map(|gate| circuit_gate(gate, circuit.wires.len(), &circuit.tables))
+            .collect::<Result<Vec<Gate>, IrError>>()?;
+        let circuit = Circuit { gates, ..circuit };
+        check_order(&circuit)?;
+        for (listed, actual, what) in [
+            (
+                &document.public_inputs,
+                circuit.inputs(Visibility::Public),
+                "public_inputs",
+            ),
+            (
+                &document.private_inputs,
+                circuit.inputs(Visibility::Private),
+                "private_inputs",
+            ),
+            (&document.outputs, circuit.outputs(), "outputs"),
+        ] {
+            if *listed != indices(&actual) {
+                return Err(invalid(&format!(
+                    "`{}` does not match the wire kinds",
+                    what
+                )));
+            }
+        }
+
+        let r1cs = document
+            .r1cs
+            .map(|r1cs| load_r1cs(r1cs, &field_modulus, &circuit))
+            .transpose()?;
+        Ok(CompiledCircuit {
+            field_modulus,
+            circuit,
+            r1cs,
+            metadata: document.metadata,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Document {
+    version: u32,
+    field: String,
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+    wires: Vec<JsonWire>,
+    public_inputs: Vec<usize>,
+    private_inputs: Vec<usize>,
+    outputs: Vec<usize>,
+    gates: Vec<JsonGate>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tables: Vec<JsonTable>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    r1cs: Option<JsonR1CS>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonWire {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    kind: JsonWireKind,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum JsonWireKind {
+    PublicInput,
+    PrivateInput,
+    Output,
+    Internal,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonGate {
+    op: String,
+    args: Vec<JsonOperand>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    out: Option<usize>,
+    /// index into `tables`, only for `lookup`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    table: Option<usize>,
+    span: [usize; 2],
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonTable {
+    name: String,
+    entries: Vec<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum JsonOperand {
+    Wire(usize),
+    Constant(String),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonR1CS {
+    num_variables: usize,
+    num_outputs: usize,
+    num_public: usize,
+    num_private: usize,
+    constraints: Vec<JsonConstraint>,
+    #[serde(default)]
+    wire_variables: Vec<Option<usize>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonConstraint {
+    a: Vec<(usize, String)>,
+    b: Vec<(usize, String)>,
+    c: Vec<(usize, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gate: Option<usize>,
+}
+
+fn invalid(message: &str) -> IrError {
+    IrError::Invalid(message.to_string())
+}
+
+fn number(s: &str) -> Result<BigInt, IrError> {
+    s.parse()
+        .map_err(|_| invalid(&format!("`{}` is not a decimal number", s)))
+}
+
+fn indices(wires: &[Wire]) -> Vec<usize> {
+    wires.iter().map(|w| w.0).collect()
+}
+
+fn json_operand(operand: &Operand) -> JsonOperand {
+    match operand {
+        Operand::Wire(wire) => JsonOperand::Wire(wire.0),
+        Operand::Constant(n) => JsonOperand::Constant(n.to_string()),
+    }
+}
+
+fn json_gate(gate: &Gate) -> JsonGate {
+    let (op, args, out) = match &gate.kind {
+        GateKind::Add { lhs, rhs, out } => ("add", vec![lhs, rhs], Some(out)),
+        GateKind::Mul { lhs, rhs, out } => ("mul", vec![lhs, rhs], Some(out)),
+        GateKind::AssertEq { lhs, rhs } => ("assert_eq", vec![lhs, rhs], None),
+        GateKind::Hint { hint, args, out } => (hint.name(), args.iter().collect(), Some(out)),
+        GateKind::Lookup { table, args } => {
+            return JsonGate {
+                op: "lookup".to_string(),
+                args: args.iter().map(|w| JsonOperand::Wire(w.0)).collect(),
+                out: None,
+                table: Some(*table),
+                span: [gate.span.start, gate.span.end],
+            }
+        }
+    };
+    JsonGate {
+        op: op.to_string(),
+        args: args.into_iter().map(json_operand).collect(),
+        out: out.map(|w| w.0),
+        table: None,
+        span: [gate.span.start, gate.span.end],
+    }
+}
+
+fn json_lc(lc: &LinearCombination) -> Vec<(usize, String)> {
+    lc.terms
+        .iter()
+        .map(|(v, coeff)| (*v, coeff.n.to_string()))
+        .collect()
+}
+
+fn circuit_table(table: &JsonTable) -> Result<LookupTable, IrError> {
+    let entries = table
+        .entries
+        .iter()
+        .map(|entry| entry.iter().map(|n| number(n)).collect())
+        .collect::<Result<Vec<Vec<BigInt>>, IrError>>()?;
+    LookupTable::check(&entries)
+        .map_err(|message| invalid(&format!("table `{}`: {}", table.name, message)))?;
+    Ok(LookupTable::new(&table.name, entries))
+}
+
+fn circuit_gate(
+    gate: &JsonGate,
+    num_wires: usize,
+    tables: &[LookupTable],
+) -> Result<Gate, IrError> {
+    let wire = |index: usize| {
+        (index < num_wires)
+            .then_some(Wire(index))
+            .ok_or_else(|| invalid(&format!("wire {} does not exist", index)))
+    };
+    let args = gate
+        .args
+        .iter()
+        .map(|arg| match arg {
+            JsonOperand::Wire(index) => wire(*index).map(Operand::Wire),
+            JsonOperand::Constant(n) => number(n).map(Operand::Constant),
+        })
+        .collect::<Result<Vec<Operand>, IrError>>()?;
+    let table = match (gate.op.as_str(), gate.table) {
+        ("lookup", Some(table)) => Some(
+            tables
+                .get(table)
+                .map(|t| (table, t.width()))
+                .ok_or_else(|| invalid(&format!("table {} does not exist", table)))?,
+        ),
+        ("lookup", None) => return Err(invalid("`lookup` needs a table")),
+        (_, Some(_)) => return Err(invalid("only `lookup` reads a table")),
+        (_, None) => None,
+    };
+    let hint = match gate.op.as_str() {
+        "div" => Some(Hint::Div),
+        "add" | "mul" | "assert_eq" | "lookup" => None,
+        name => {
+            Some(Hint::builtin(name).ok_or_else(|| invalid(&format!("unknown gate `{}`", name)))?)
+        }
+    };
+    let arity = match (hint, table) {
+        (Some(hint), _) => hint.arity(),
+        (None, Some((_, width))) => width,
+        (None, None) => 2,
+    };
+    if args.len() != arity {
+        return Err(invalid(&format!(
+            "`{}` takes {} argument(s), found {}",
+            gate.op,
+            arity,
+            args.len()
+        )));
+    }
+    let out = match (gate.op.as_str(), gate.out) {
+        ("assert_eq" | "lookup", None) => None,
+        (op @ ("assert_eq" | "lookup"), Some(_)) => {
+            return Err(invalid(&format!("`{}` has no output", op)))
+        }
+        (_, Some(out)) => Some(wire(out)?),
+        (op, None) => return Err(invalid(&format!("`{}` needs an output", op))),
+    };
+    let mut args = args.into_iter();
+    let kind = match (hint, out, table) {
+        (_, _, Some((table, _))) => GateKind::Lookup {
+            table,
+            args: args
+                .map(|arg| arg.wire())
+                .collect::<Option<Vec<Wire>>>()
+                .ok_or_else(|| invalid("`lookup` only reads wires"))?,
+        },
+        (Some(hint), Some(out), None) => GateKind::Hint {
+            hint,
+            args: args.collect(),
+            out,
+        },
+        (None, out, None) => {
+            let (lhs, rhs) = (args.next().unwrap(), args.next().unwrap());
+            match (gate.op.as_str(), out) {
+                ("add", Some(out)) => GateKind::Add { lhs, rhs, out },
+                ("mul", Some(out)) => GateKind::Mul { lhs, rhs, out },
+                _ => GateKind::AssertEq { lhs, rhs },
+            }
+        }
+        (Some(_), None, None) => unreachable!("hints always have an output"),
+    };
+    let [start, end] = gate.span;
+    if start > end {
+        return Err(invalid(&format!(
+            "span {}..{} ends before it starts",
+            start, end
+        )));
+    }
+    Ok(Gate {
+        kind,
+        span: Span::new(start, end),
+    })
+}
+
+/// every wire is written by at most one gate and read only after it is written, the
+/// witness generator and the backends evaluate gates in order
+fn check_order(circuit: &Circuit) -> Result<(), IrError> {
+    let mut defined: Vec<bool> = circuit
+        .wires
+        .iter()
+        .map(|info| matches!(info.kind, WireKind::Input(_)))
+        .collect();
+    for (index, gate) in circuit.gates.iter().enumerate() {
+        let (reads, out): (Vec<Wire>, _) = match &gate.kind {
+            GateKind::Add { lhs, rhs, out } | GateKind::Mul { lhs, rhs, out } => (
+                [lhs, rhs].into_iter().filter_map(Operand::wire).collect(),
+                Some(out),
+            ),
+            GateKind::AssertEq { lhs, rhs } => (
+                [lhs, rhs].into_iter().filter_map(Operand::wire).collect(),
+                None,
+            ),
+            GateKind::Hint { args, out, .. } => {
+                (args.iter().filter_map(Operand::wire).collect(), Some(out))
+            }
+            GateKind::Lookup { args, .. } => (args.clone(), None),
+        };
+        if let Some(wire) = reads.into_iter().find(|wire| !defined[wire.0]) {
+            return Err(invalid(&format!(
+                "gate {} reads wire {} before it is written",
+                index, wire.0
+            )));
+        }
+        if let Some(out) = out {
+            if defined[out.0] {
+                return Err(invalid(&format!(
+                    "gate {} writes wire {}, which already has a value",
+                    index, out.0
+                )));
+            }
+            defined[out.0] = true;
+        }
+    }
+    Ok(())
+}
+
+fn load_r1cs(r1cs: JsonR1CS, field_modulus: &BigInt, circuit: &Circuit) -> Result<R1CS, IrError> {
+    if 1 + r1cs.num_outputs + r1cs.num_public + r1cs.num_private > r1cs.num_variables {
+        return Err(invalid("more inputs and outputs than variables"));
+    }
+    if !r1cs.wire_variables.is_empty() && r1cs.wire_variables.len() != circuit.wires.len() {
+        return Err(invalid("`wire_variables` needs one entry per wire"));
+    }
+    // every wire gets a variable of its own, variable 0 is the constant one
+    let mut assigned = vec![false; r1cs.num_variables];
+    for v in r1cs.wire_variables.iter().flatten() {
+        if *v == R1CS::ONE || *v >= r1cs.num_variables {
+            return Err(invalid(&format!(
+                "`wire_variables` refers to variable {}, which is not a wire",
+                v
+            )));
+        }
+        if std::mem::replace(&mut assigned[*v], true) {
+            return Err(invalid(&format!(
+                "`wire_variables` gives variable {} to two wires",
+                v
+            )));
+        }
+    }
+    let lc = |terms: &[(usize, String)]| -> Result<LinearCombination, IrError> {
+        let mut lc = LinearCombination::default();
+        for (v, coeff) in terms {
+            if *v >= r1cs.num_variables {
+                return Err(invalid(&format!("variable {} does not exist", v)));
+            }
+            lc.add_term(*v, fq(number(coeff)?, field_modulus));
+        }
+        Ok(lc)
+    };
+    let mut out = R1CS {
+        field_modulus: field_modulus.clone(),
+        num_outputs: r1cs.num_outputs,
+        num_public: r1cs.num_public,
+        num_private: r1cs.num_private,
+        num_variables: r1cs.num_variables,
+        a: Vec::new(),
+        b: Vec::new(),
+        c: Vec::new(),
+        wire_variables: r1cs.wire_variables.clone(),
+        constraint_gates: Vec::new(),
+    };
+    for constraint in &r1cs.constraints {
+        if constraint.gate.is_some_and(|g| g >= circuit.gates.len()) {
+            return Err(invalid("constraint refers to a missing gate"));
+        }
+        out.push(lc(&constraint.a)?, lc(&constraint.b)?, lc(&constraint.c)?);
+        *out.constraint_gates.last_mut().unwrap() = constraint.gate;
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use super::{CompiledCircuit, IrError};
+    use crate::{flatten, generate_witness, parse, R1CS};
+
+    #[test]
+    fn round_trips_circuit_and_r1cs() {
+        let source = "public y;\nprivate x, d;\nlet q = x / d;\nassert_eq(q * q + 1, y);\noutput r = sqrt(x);";
+        let circuit = flatten(&parse(source).unwrap()).unwrap();
+        let r1cs = R1CS::from_circuit_merged(&circuit, BigInt::from(1009)).unwrap();
+        let compiled = CompiledCircuit::new(circuit, r1cs);
+        let json = compiled.to_json();
+        assert!(json.contains("\"op\": \"div\""));
+        let loaded = CompiledCircuit::from_json(&json).unwrap();
+        assert_eq!(loaded, compiled);
+
+        // the loaded circuit still proves
+        let r1cs = loaded.r1cs.unwrap();
+        let witness =
+            generate_witness(&loaded.circuit, &r1cs, &[65.into()], &[16.into(), 2.into()]).unwrap();
+        assert!(r1cs.is_satisfied(&witness));
+
+        // a loaded witness would otherwise write two wires, or a wire and one, to the
+        // same variable
+        for (variables, message) in [
+            (
+                "[0, 1, 2]",
+                "`wire_variables` refers to variable 0, which is not a wire",
+            ),
+            (
+                "[1, 2, 99]",
+                "`wire_variables` refers to variable 99, which is not a wire",
+            ),
+            (
+                "[1, 2, 2]",
+                "`wire_variables` gives variable 2 to two wires",
+            ),
+        ] {
+            let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+            let wires = &mut value["r1cs"]["wire_variables"];
+            let mut replaced: Vec<serde_json::Value> = serde_json::from_str(variables).unwrap();
+            replaced.resize(wires.as_array().unwrap().len(), serde_json::Value::Null);
+            *wires = replaced.into();
+            assert_eq!(
+                CompiledCircuit::from_json(&value.to_string()),
+                Err(IrError::Invalid(message.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn round_trips_lookup_tables() {
+        let source = "table bits = [0, 1];\ntable x = xor(1);\nprivate a, b;\nlookup(bits, a);\nlookup(x, a, b, 1);\noutput o = a * b;";
+        let compiled = CompiledCircuit {
+            field_modulus: BigInt::from(1009),
+            circuit: flatten(&parse(source).unwrap()).unwrap(),
+            r1cs: None,
+            metadata: Default::default(),
+        };
+        let json = compiled.to_json();
+        assert!(json.contains("\"op\": \"lookup\""));
+        assert_eq!(CompiledCircuit::from_json(&json).unwrap(), compiled);
+
+        for (gate, message) in [
+            (
+                r#"{"op": "lookup", "args": [0], "span": [0, 1]}"#,
+                "`lookup` needs a table",
+            ),
+            (
+                r#"{"op": "lookup", "args": [0], "table": 0, "span": [0, 1]}"#,
+                "table 0 does not exist",
+            ),
+            (
+                r#"{"op": "mul", "args": [0, 0], "out": 1, "table": 0, "span": [0, 1]}"#,
+                "only `lookup` reads a table",
+            ),
+        ] {
+            assert_eq!(with_gates(gate), Err(IrError::Invalid(message.to_string())));
+        }
+    }
+
+    #[test]
+    fn rejects_other_versions_and_bad_indices() {
+        let json = r#"{"version": 2, "field": "7"}"#;
+        assert_eq!(
+            CompiledCircuit::from_json(json),
+            Err(IrError::UnsupportedVersion(2))
+        );
+        let json = r#"{
+            "version": 1, "field": "7",
+            "wires": [{"name": "x", "kind": "private_input"}],
+            "public_inputs": [], "private_inputs": [0], "outputs": [],
+            "gates": [{"op": "mul", "args": [0, "3"], "out": 4, "span": [0, 1]}]
+        }"#;
+        assert_eq!(
+            CompiledCircuit::from_json(json),
+            Err(IrError::Invalid("wire 4 does not exist".to_string()))
+        );
+        assert!(matches!(
+            CompiledCircuit::from_json("{\"version\": 1}"),
+            Err(IrError::Json(_))
+        ));
+    }
+
+    fn with_gates(gates: &str) -> Result<CompiledCircuit, IrError> {
+        CompiledCircuit::from_json(&format!(
+            r#"{{
+                "version": 1, "field": "7",
+                "wires": [{{"name": "x", "kind": "private_input"}}, {{"kind": "internal"}},
+                          {{"name": "y", "kind": "output"}}],
+                "public_inputs": [], "private_inputs": [0], "outputs": [2],
+                "gates": [{}]
+            }}"#,
+            gates
+        ))
+    }
+
+    #[test]
+    fn rejects_gates_out_of_order() {
+        let square = r#"{"op": "mul", "args": [0, 0], "out": 1, "span": [0, 5]}"#;
+        let double = r#"{"op": "add", "args": [1, 1], "out": 2, "span": [6, 9]}"#;
+        assert!(with_gates(&format!("{}, {}", square, double)).is_ok());
+        assert_eq!(
+            with_gates(&format!("{}, {}", double, square)),
+            Err(IrError::Invalid(
+                "gate 0 reads wire 1 before it is written".to_string()
+            ))
+        );
+        assert_eq!(
+            with_gates(&format!("{}, {}, {}", square, square, double)),
+            Err(IrError::Invalid(
+                "gate 1 writes wire 1, which already has a value".to_string()
+            ))
+        );
+        let reversed = square.replace("[0, 5]", "[5, 1]");
+        assert_eq!(
+            with_gates(&format!("{}, {}", reversed, double)),
+            Err(IrError::Invalid(
+                "span 5..1 ends before it starts".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,220 @@
+use std::fmt;
+
+use num_bigint::BigInt;
+
+use crate::ast::Span;
+use crate::error::CompileError;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    Ident(String),
+    Number(BigInt),
+    Public,
+    Private,
+    Output,
+    Let,
+    AssertEq,
+    If,
+    Then,
+    Else,
+    Fn,
+    Return,
+    For,
+    In,
+    Mut,
+    Table,
+    Lookup,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Equals,
+    Comma,
+    Semicolon,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
+    DotDot,
+    Eof,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenKind::Ident(name) => write!(f, "`{}`", name),
+            TokenKind::Number(n) => write!(f, "`{}`", n),
+            TokenKind::Public => write!(f, "`public`"),
+            TokenKind::Private => write!(f, "`private`"),
+            TokenKind::Output => write!(f, "`output`"),
+            TokenKind::Let => write!(f, "`let`"),
+            TokenKind::AssertEq => write!(f, "`assert_eq`"),
+            TokenKind::If => write!(f, "`if`"),
+            TokenKind::Then => write!(f, "`then`"),
+            TokenKind::Else => write!(f, "`else`"),
+            TokenKind::Fn => write!(f, "`fn`"),
+            TokenKind::Return => write!(f, "`return`"),
+            TokenKind::For => write!(f, "`for`"),
+            TokenKind::In => write!(f, "`in`"),
+            TokenKind::Mut => write!(f, "`mut`"),
+            TokenKind::Table => write!(f, "`table`"),
+            TokenKind::Lookup => write!(f, "`lookup`"),
+            TokenKind::Plus => write!(f, "`+`"),
+            TokenKind::Minus => write!(f, "`-`"),
+            TokenKind::Star => write!(f, "`*`"),
+            TokenKind::Slash => write!(f, "`/`"),
+            TokenKind::Equals => write!(f, "`=`"),
+            TokenKind::Comma => write!(f, "`,`"),
+            TokenKind::Semicolon => write!(f, "`;`"),
+            TokenKind::LParen => write!(f, "`(`"),
+            TokenKind::RParen => write!(f, "`)`"),
+            TokenKind::LBracket => write!(f, "`[`"),
+            TokenKind::RBracket => write!(f, "`]`"),
+            TokenKind::LBrace => write!(f, "`{{`"),
+            TokenKind::RBrace => write!(f, "`}}`"),
+            TokenKind::DotDot => write!(f, "`..`"),
+            TokenKind::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+/// Split circuit source into tokens, `//` starts a comment that runs to the end of the line
+pub fn tokenize(source: &str) -> Result<Vec<Token>, CompileError> {
+    let bytes = source.as_bytes();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let c = source[pos..]
+            .chars()
+            .next()
+            .expect("pos is on a char boundary");
+        if c.is_ascii_whitespace() {
+            pos += 1;
+            continue;
+        }
+        if source[pos..].starts_with("//") {
+            while pos < bytes.len() && bytes[pos] != b'\n' {
+                pos += 1;
+            }
+            continue;
+        }
+        let start = pos;
+        let kind = if c.is_ascii_digit() {
+            while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+                pos += 1;
+            }
+            TokenKind::Number(
+                source[start..pos]
+                    .parse::<BigInt>()
+                    .expect("digits always parse as BigInt"),
+            )
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            while pos < bytes.len() && (bytes[pos].is_ascii_alphanumeric() || bytes[pos] == b'_') {
+                pos += 1;
+            }
+            match &source[start..pos] {
+                "public" => TokenKind::Public,
+                "private" => TokenKind::Private,
+                "output" => TokenKind::Output,
+                "let" => TokenKind::Let,
+                "assert_eq" => TokenKind::AssertEq,
+                "if" => TokenKind::If,
+                "then" => TokenKind::Then,
+                "else" => TokenKind::Else,
+                "fn" => TokenKind::Fn,
+                "return" => TokenKind::Return,
+                "for" => TokenKind::For,
+                "in" => TokenKind::In,
+                "mut" => TokenKind::Mut,
+                "table" => TokenKind::Table,
+                "lookup" => TokenKind::Lookup,
+                word => TokenKind::Ident(word.to_string()),
+            }
+        } else if source[pos..].starts_with("..") {
+            pos += 2;
+            TokenKind::DotDot
+        } else {
+            pos += c.len_utf8();
+            match c {
+                '+' => TokenKind::Plus,
+                '-' => TokenKind::Minus,
+                '*' => TokenKind::Star,
+                '/' => TokenKind::Slash,
+                '=' => TokenKind::Equals,
+                ',' => TokenKind::Comma,
+                ';' => TokenKind::Semicolon,
+                '(' => TokenKind::LParen,
+                ')' => TokenKind::RParen,
+                '[' => TokenKind::LBracket,
+                ']' => TokenKind::RBracket,
+                '{' => TokenKind::LBrace,
+                '}' => TokenKind::RBrace,
+                _ => {
+                    return Err(CompileError::Parse {
+                        message: format!("unexpected character `{}`", c),
+                        span: Span::new(start, pos),
+                    })
+                }
+            }
+        };
+        tokens.push(Token {
+            kind,
+            span: Span::new(start, pos),
+        });
+    }
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        span: Span::new(source.len(), source.len()),
+    });
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tokenize, TokenKind};
+    use crate::ast::Span;
+
+    #[test]
+    fn keywords_and_comments() {
+        let tokens = tokenize("public a; // the input\nlet b = a * 2;").unwrap();
+        let kinds: Vec<TokenKind> = tokens.into_iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Public,
+                TokenKind::Ident("a".into()),
+                TokenKind::Semicolon,
+                TokenKind::Let,
+                TokenKind::Ident("b".into()),
+                TokenKind::Equals,
+                TokenKind::Ident("a".into()),
+                TokenKind::Star,
+                TokenKind::Number(2.into()),
+                TokenKind::Semicolon,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn unexpected_character() {
+        let err = tokenize("let a = 1 % 2;").unwrap_err();
+        assert_eq!(err.span().unwrap().start, 10);
+        // a multi-byte character is reported whole
+        let source = "let a = 1 € 2;";
+        let err = tokenize(source).unwrap_err();
+        assert_eq!(err.to_string(), "unexpected character `€`");
+        assert_eq!(err.span(), Some(Span::new(10, 13)));
+        assert!(err
+            .render(source)
+            .ends_with("1 | let a = 1 € 2;\n  |           ^\n"));
+    }
+}
@@ -1 +1,44 @@
 // a compiler for arithmetic circuits
+pub mod analysis;
+pub mod ast;
+pub mod bristol;
+pub mod circom;
+pub mod circuit;
+pub mod constraint_system;
+pub mod error;
+pub mod expand;
+pub mod field;
+pub mod flatten;
+pub mod gadgets;
+pub mod interpreter;
+pub mod ir;
+pub mod lexer;
+pub mod mock;
+pub mod optimizer;
+pub mod parser;
+pub mod plonk;
+pub mod qap;
+pub mod r1cs;
+pub mod stats;
+#[cfg(test)]
+mod test_utils;
+pub mod witness;
+
+pub use analysis::{analyze, Finding};
+pub use bristol::from_bristol;
+pub use circuit::Circuit;
+pub use constraint_system::{ConstraintSystem, R1CSBuilder, Variable, WitnessBuilder};
+pub use error::CompileError;
+pub use expand::expand;
+pub use field::Field;
+pub use flatten::flatten;
+pub use interpreter::{evaluate, EvalError};
+pub use ir::CompiledCircuit;
+pub use mock::{Failure, MockProver, VerifyError};
+pub use optimizer::{optimize, OptimizationReport};
+pub use parser::parse;
+pub use plonk::PlonkCircuit;
+pub use qap::{QapError, QAP};
+pub use r1cs::R1CS;
+pub use stats::{stats, to_dot, CircuitStats};
+pub use witness::{generate_witness, wire_values, WitnessError};
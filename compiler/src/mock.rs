@@ -0,0 +1,247 @@
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_traits::One;
+use rust_ecc::field::FQ;
+
+use crate::ast::Span;
+use crate::circuit::Circuit;
+use crate::error::render;
+use crate::r1cs::{LinearCombination, R1CS};
+
+/// A constraint the witness violates, with enough context to find out why
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Failure {
+    pub constraint: usize,
+    /// gate the constraint was lowered from
+    pub gate: Option<usize>,
+    /// source of that gate
+    pub span: Option<Span>,
+    /// values of `A·w`, `B·w` and `C·w`
+    pub a: BigInt,
+    pub b: BigInt,
+    pub c: BigInt,
+    /// name and value of every variable the constraint mentions, in variable order
+    pub variables: Vec<(String, BigInt)>,
+}
+
+impl Failure {
+    /// the failure with the source line of its gate underlined, followed by the values
+    pub fn render(&self, source: &str) -> String {
+        let message = self.headline();
+        let mut out = match self.span {
+            Some(span) => render(source, span, &message),
+            None => format!("error: {}\n", message),
+        };
+        for (name, value) in &self.variables {
+            out.push_str(&format!("  = {} = {}\n", name, value));
+        }
+        out
+    }
+
+    fn headline(&self) -> String {
+        let gate = self
+            .gate
+            .map_or(String::new(), |g| format!(" from gate {}", g));
+        format!(
+            "constraint {}{} is not satisfied: {} * {} != {}",
+            self.constraint, gate, self.a, self.b, self.c
+        )
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let values: Vec<String> = self
+            .variables
+            .iter()
+            .map(|(name, value)| format!("{} = {}", name, value))
+            .collect();
+        write!(f, "{} ({})", self.headline(), values.join(", "))
+    }
+}
+
+/// Why `MockProver::verify` rejected a witness
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerifyError {
+    /// the witness does not have one value per variable
+    WitnessLength { expected: usize, found: usize },
+    /// the first variable is not the constant one
+    NotOne { value: BigInt },
+    /// every violated constraint in order
+    Unsatisfied(Vec<Failure>),
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VerifyError::WitnessLength { expected, found } => {
+                write!(f, "witness has {} variables, expected {}", found, expected)
+            }
+            VerifyError::NotOne { value } => {
+                write!(f, "witness starts with {} instead of one", value)
+            }
+            VerifyError::Unsatisfied(failures) => {
+                let lines: Vec<String> = failures.iter().map(|f| f.to_string()).collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Checks a witness against every constraint and explains the ones that fail
+///
+/// Meant for tests and debugging: where `R1CS::is_satisfied` only answers yes or no,
+/// `verify` lists each broken constraint with the gate and source it came from.
+pub struct MockProver<'a> {
+    r1cs: &'a R1CS,
+    circuit: Option<&'a Circuit>,
+    names: Vec<String>,
+}
+
+impl<'a> MockProver<'a> {
+    /// prover for an R1CS lowered from `circuit`, variables are named after its wires
+    pub fn new(circuit: &'a Circuit, r1cs: &'a R1CS) -> MockProver<'a> {
+        MockProver {
+            r1cs,
+            circuit: Some(circuit),
+            names: r1cs.variable_names(circuit),
+        }
+    }
+
+    /// prover for a constraint system without a circuit, e.g. one built with
+    /// `R1CSBuilder` whose `variable_names` can be passed here
+    pub fn with_names(r1cs: &'a R1CS, names: Vec<String>) -> MockProver<'a> {
+        assert_eq!(names.len(), r1cs.num_variables, "one name per variable");
+        MockProver {
+            r1cs,
+            circuit: None,
+            names,
+        }
+    }
+
+    /// every violated constraint in order, a witness of the wrong length or one that
+    /// does not start with the constant one fails before any constraint is checked
+    pub fn verify(&self, witness: &[FQ]) -> Result<(), VerifyError> {
+        if witness.len() != self.r1cs.num_variables {
+            return Err(VerifyError::WitnessLength {
+                expected: self.r1cs.num_variables,
+                found: witness.len(),
+            });
+        }
+        if !witness[R1CS::ONE].n.is_one() {
+            return Err(VerifyError::NotOne {
+                value: witness[R1CS::ONE].n.clone(),
+            });
+        }
+        let failures: Vec<Failure> = (0..self.r1cs.num_constraints())
+            .filter(|i| !self.r1cs.is_constraint_satisfied(*i, witness))
+            .map(|i| self.failure(i, witness))
+            .collect();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(VerifyError::Unsatisfied(failures))
+        }
+    }
+
+    fn failure(&self, constraint: usize, witness: &[FQ]) -> Failure {
+        let p = &self.r1cs.field_modulus;
+        let rows = [
+            &self.r1cs.a[constraint],
+            &self.r1cs.b[constraint],
+            &self.r1cs.c[constraint],
+        ];
+        let [a, b, c] = rows.map(|lc: &LinearCombination| lc.evaluate(witness, p).n);
+        let mut variables: Vec<usize> = rows
+            .iter()
+            .flat_map(|lc| lc.terms.iter().map(|(v, _)| *v))
+            .filter(|v| *v != R1CS::ONE)
+            .collect();
+        variables.sort_unstable();
+        variables.dedup();
+        let gate = self.r1cs.constraint_gates[constraint];
+        Failure {
+            constraint,
+            gate,
+            span: gate
+                .zip(self.circuit)
+                .map(|(gate, circuit)| circuit.gates[gate].span),
+            a,
+            b,
+            c,
+            variables: variables
+                .into_iter()
+                .map(|v| (self.names[v].clone(), witness[v].n.clone()))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use super::{MockProver, VerifyError};
+    use crate::constraint_system::{ConstraintSystem, R1CSBuilder};
+    use crate::r1cs::fq;
+    use crate::test_utils::ints;
+    use crate::{flatten, generate_witness, parse, R1CS};
+
+    #[test]
+    fn points_at_the_failing_line() {
+        let source = "private x;\nlet y = x * x;\noutput z = y + 1;";
+        let circuit = flatten(&parse(source).unwrap()).unwrap();
+        let p = BigInt::from(101);
+        let r1cs = R1CS::from_circuit(&circuit, p.clone()).unwrap();
+        let mut witness = generate_witness(&circuit, &r1cs, &[], &ints(&[3])).unwrap();
+        let prover = MockProver::new(&circuit, &r1cs);
+        assert_eq!(prover.verify(&witness), Ok(()));
+
+        // a prover claiming 3 * 3 = 10
+        let names = r1cs.variable_names(&circuit);
+        let y = names.iter().position(|n| n == "y").unwrap();
+        witness[y] = fq(10.into(), &p);
+        let Err(VerifyError::Unsatisfied(failures)) = prover.verify(&witness) else {
+            panic!("3 * 3 = 10 was accepted");
+        };
+        assert_eq!(failures.len(), 2);
+        assert_eq!(
+            failures[0].render(source),
+            "error: constraint 0 from gate 0 is not satisfied: 3 * 3 != 10\n --> 2:9\n  |\n2 | let y = x * x;\n  |         ^^^^^\n  = x = 3\n  = y = 10\n"
+        );
+    }
+
+    #[test]
+    fn builder_constraints_use_allocation_names() {
+        let p = BigInt::from(101);
+        let mut builder = R1CSBuilder::new(p.clone()).unwrap();
+        let a = builder.alloc_input("a", None);
+        let b = builder.alloc("b", None);
+        builder.enforce(builder.lc(a), builder.lc(a), builder.lc(b));
+        let names = builder.variable_names();
+        let r1cs = builder.into_r1cs();
+        let prover = MockProver::with_names(&r1cs, names);
+        let witness = [1, 4, 15].map(|v| fq(BigInt::from(v), &p));
+        assert_eq!(
+            prover.verify(&witness).unwrap_err().to_string(),
+            "constraint 0 is not satisfied: 4 * 4 != 15 (a = 4, b = 15)"
+        );
+
+        // malformed witnesses are errors, not panics
+        assert_eq!(
+            prover.verify(&witness[..2]),
+            Err(VerifyError::WitnessLength {
+                expected: 3,
+                found: 2
+            })
+        );
+        let witness = [0, 4, 16].map(|v| fq(BigInt::from(v), &p));
+        assert_eq!(
+            prover.verify(&witness).unwrap_err().to_string(),
+            "witness starts with 0 instead of one"
+        );
+    }
+}
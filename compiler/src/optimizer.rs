@@ -0,0 +1,309 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_traits::{One, Zero};
+use rust_ecc::math::modulo;
+
+use crate::circuit::{Circuit, Gate, GateKind, Operand, Wire, WireKind};
+use crate::error::CompileError;
+use crate::r1cs::R1CS;
+
+/// Constraint and variable counts of the naive and the optimized lowering
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OptimizationReport {
+    pub constraints_before: usize,
+    pub constraints_after: usize,
+    pub variables_before: usize,
+    pub variables_after: usize,
+}
+
+impl fmt::Display for OptimizationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "constraints: {} -> {}, variables: {} -> {}",
+            self.constraints_before,
+            self.constraints_after,
+            self.variables_before,
+            self.variables_after
+        )
+    }
+}
+
+/// Run every pass and lower the result with linear combination merging
+///
+/// The returned circuit is the one to generate witnesses for, it shares wire indices
+/// with the input but gates that were folded away no longer compute their wires.
+/// Fails on a constant that does not fit in the field, before folding reduces it.
+pub fn optimize(
+    circuit: &Circuit,
+    field_modulus: impl Into<BigInt>,
+) -> Result<(Circuit, R1CS, OptimizationReport), CompileError> {
+    let field_modulus = field_modulus.into();
+    let before = R1CS::from_circuit(circuit, field_modulus.clone())?;
+    let optimized = eliminate_common_subexpressions(&fold_constants(circuit, &field_modulus));
+    let r1cs = R1CS::from_circuit_merged(&optimized, field_modulus)?;
+    let report = OptimizationReport {
+        constraints_before: before.num_constraints(),
+        constraints_after: r1cs.num_constraints(),
+        variables_before: before.num_variables,
+        variables_after: r1cs.num_variables,
+    };
+    Ok((optimized, r1cs, report))
+}
+
+/// Evaluate gates whose operands are all constant and drop additions of zero and
+/// multiplications by one or zero, outputs always keep their gate
+///
+/// Lookups only read wires, a folded wire one reads is computed again as `k * 1`.
+pub fn fold_constants(circuit: &Circuit, field_modulus: &BigInt) -> Circuit {
+    let mut substitutions: HashMap<Wire, Operand> = HashMap::new();
+    let mut restored: HashSet<Wire> = HashSet::new();
+    let mut gates: Vec<Gate> = Vec::new();
+    for gate in &circuit.gates {
+        let resolve = |operand: &Operand| match resolve(operand, &substitutions) {
+            Operand::Constant(n) => Operand::Constant(modulo(&n, field_modulus)),
+            wire => wire,
+        };
+        let kind = match &gate.kind {
+            GateKind::Add { lhs, rhs, out } | GateKind::Mul { lhs, rhs, out } => {
+                let is_mul = matches!(gate.kind, GateKind::Mul { .. });
+                let (lhs, rhs) = (resolve(lhs), resolve(rhs));
+                let folded = match (&lhs, &rhs) {
+                    (Operand::Constant(a), Operand::Constant(b)) if is_mul => {
+                        Some(Operand::Constant(modulo(&(a * b), field_modulus)))
+                    }
+                    (Operand::Constant(a), Operand::Constant(b)) => {
+                        Some(Operand::Constant(modulo(&(a + b), field_modulus)))
+                    }
+                    (Operand::Constant(k), x) | (x, Operand::Constant(k)) => {
+                        if is_mul && k.is_zero() {
+                            Some(Operand::Constant(BigInt::zero()))
+                        } else if (is_mul && k.is_one()) || (!is_mul && k.is_zero()) {
+                            Some(x.clone())
+                        } else {
+                            None
+                        }
+                    }
+                    _ => None,
+                };
+                match folded {
+                    Some(value) if circuit.wires[out.0].kind == WireKind::Internal => {
+                        substitutions.insert(*out, value);
+                        continue;
+                    }
+                    _ if is_mul => GateKind::Mul {
+                        lhs,
+                        rhs,
+                        out: *out,
+                    },
+                    _ => GateKind::Add {
+                        lhs,
+                        rhs,
+                        out: *out,
+                    },
+                }
+            }
+            GateKind::AssertEq { lhs, rhs } => {
+                let (lhs, rhs) = (resolve(lhs), resolve(rhs));
+                if lhs == rhs {
+                    continue;
+                }
+                GateKind::AssertEq { lhs, rhs }
+            }
+            GateKind::Hint { hint, args, out } => GateKind::Hint {
+                hint: *hint,
+                args: args.iter().map(resolve).collect(),
+                out: *out,
+            },
+            GateKind::Lookup { table, args } => {
+                let mut wires = Vec::new();
+                for wire in args {
+                    match resolve(&Operand::Wire(*wire)) {
+                        Operand::Wire(wire) => wires.push(wire),
+                        constant => {
+                            if restored.insert(*wire) {
+                                gates.push(Gate {
+                                    kind: GateKind::Mul {
+                                        lhs: constant,
+                                        rhs: Operand::Constant(BigInt::one()),
+                                        out: *wire,
+                                    },
+                                    span: gate.span,
+                                });
+                            }
+                            wires.push(*wire);
+                        }
+                    }
+                }
+                GateKind::Lookup {
+                    table: *table,
+                    args: wires,
+                }
+            }
+        };
+        gates.push(Gate {
+            kind,
+            span: gate.span,
+        });
+    }
+    Circuit {
+        wires: circuit.wires.clone(),
+        gates,
+        tables: circuit.tables.clone(),
+    }
+}
+
+/// Reuse the output of an earlier gate computing the same operation on the same
+/// operands, additions and multiplications are matched regardless of operand order
+pub fn eliminate_common_subexpressions(circuit: &Circuit) -> Circuit {
+    let mut substitutions: HashMap<Wire, Operand> = HashMap::new();
+    let mut seen: HashMap<(bool, Operand, Operand), Wire> = HashMap::new();
+    let mut assertions: HashSet<(Operand, Operand)> = HashSet::new();
+    let mut lookups: HashSet<(usize, Vec<Wire>)> = HashSet::new();
+    let mut gates: Vec<Gate> = Vec::new();
+    for gate in &circuit.gates {
+        let kind = match &gate.kind {
+            GateKind::Add { lhs, rhs, out } | GateKind::Mul { lhs, rhs, out } => {
+                let is_mul = matches!(gate.kind, GateKind::Mul { .. });
+                let (lhs, rhs) = sorted(resolve(lhs, &substitutions), resolve(rhs, &substitutions));
+                let key = (is_mul, lhs.clone(), rhs.clone());
+                match seen.get(&key) {
+                    Some(previous) if circuit.wires[out.0].kind == WireKind::Internal => {
+                        substitutions.insert(*out, Operand::Wire(*previous));
+                        continue;
+                    }
+                    _ => {
+                        seen.entry(key).or_insert(*out);
+                    }
+                }
+                if is_mul {
+                    GateKind::Mul {
+                        lhs,
+                        rhs,
+                        out: *out,
+                    }
+                } else {
+                    GateKind::Add {
+                        lhs,
+                        rhs,
+                        out: *out,
+                    }
+                }
+            }
+            GateKind::AssertEq { lhs, rhs } => {
+                let (lhs, rhs) = sorted(resolve(lhs, &substitutions), resolve(rhs, &substitutions));
+                if !assertions.insert((lhs.clone(), rhs.clone())) {
+                    continue;
+                }
+                GateKind::AssertEq { lhs, rhs }
+            }
+            GateKind::Hint { hint, args, out } => GateKind::Hint {
+                hint: *hint,
+                args: args.iter().map(|a| resolve(a, &substitutions)).collect(),
+                out: *out,
+            },
+            GateKind::Lookup { table, args } => {
+                // substitutions here only ever point at earlier wires
+                let args: Vec<Wire> = args
+                    .iter()
+                    .map(|wire| match substitutions.get(wire) {
+                        Some(Operand::Wire(previous)) => *previous,
+                        _ => *wire,
+                    })
+                    .collect();
+                if !lookups.insert((*table, args.clone())) {
+                    continue;
+                }
+                GateKind::Lookup {
+                    table: *table,
+                    args,
+                }
+            }
+        };
+        gates.push(Gate {
+            kind,
+            span: gate.span,
+        });
+    }
+    Circuit {
+        wires: circuit.wires.clone(),
+        gates,
+        tables: circuit.tables.clone(),
+    }
+}
+
+fn resolve(operand: &Operand, substitutions: &HashMap<Wire, Operand>) -> Operand {
+    match operand {
+        Operand::Wire(wire) => substitutions
+            .get(wire)
+            .cloned()
+            .unwrap_or_else(|| operand.clone()),
+        constant => constant.clone(),
+    }
+}
+
+fn sorted(lhs: Operand, rhs: Operand) -> (Operand, Operand) {
+    if lhs <= rhs {
+        (lhs, rhs)
+    } else {
+        (rhs, lhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use super::{eliminate_common_subexpressions, fold_constants, optimize};
+    use crate::test_utils::ints;
+    use crate::{flatten, generate_witness, parse};
+
+    #[test]
+    fn linear_merging() {
+        let source = "public out;\nprivate x;\nlet y = x * x * x + x + 5;\nassert_eq(y, out);";
+        let circuit = flatten(&parse(source).unwrap()).unwrap();
+        let (optimized, r1cs, report) = optimize(&circuit, BigInt::from(101)).unwrap();
+        assert_eq!(report.to_string(), "constraints: 5 -> 3, variables: 7 -> 5");
+        let witness = generate_witness(&optimized, &r1cs, &ints(&[35]), &ints(&[3])).unwrap();
+        assert!(r1cs.is_satisfied(&witness));
+        assert!(generate_witness(&optimized, &r1cs, &ints(&[36]), &ints(&[3])).is_err());
+    }
+
+    #[test]
+    fn constant_folding() {
+        let source = "private x;\nlet k = 2 * 3 - 6;\noutput o = x * (k + 1) * 4 + k;";
+        let circuit = flatten(&parse(source).unwrap()).unwrap();
+        let folded = fold_constants(&circuit, &BigInt::from(101));
+        assert_eq!(folded.to_string(), "w6 = x * 4\no = w6 + 0\n");
+        let (optimized, r1cs, report) = optimize(&circuit, BigInt::from(101)).unwrap();
+        assert_eq!(report.constraints_after, 1);
+        let witness = generate_witness(&optimized, &r1cs, &[], &ints(&[5])).unwrap();
+        assert_eq!(witness[r1cs.output_variables().start].n, BigInt::from(20));
+    }
+
+    #[test]
+    fn lookups_keep_their_wires() {
+        let source = "table bits = [0, 1];\nprivate x;\nlet k = 2 - 1;\nlookup(bits, k);\nlookup(bits, x);\nlookup(bits, x * 1);\noutput o = x * x;";
+        let circuit = flatten(&parse(source).unwrap()).unwrap();
+        let optimized =
+            eliminate_common_subexpressions(&fold_constants(&circuit, &BigInt::from(101)));
+        // the folded `k` is computed again, `x * 1` is `x` and its lookup a repeat
+        assert_eq!(
+            optimized.to_string(),
+            "k = 1 * 1\nlookup bits(k)\nlookup bits(x)\no = x * x\n"
+        );
+    }
+
+    #[test]
+    fn common_subexpressions() {
+        let source = "public out;\nprivate x, y;\nlet a = x * y;\nlet b = y * x;\nassert_eq(a + b, out);\nassert_eq(b + a, out);";
+        let circuit = flatten(&parse(source).unwrap()).unwrap();
+        let (optimized, r1cs, report) = optimize(&circuit, BigInt::from(101)).unwrap();
+        assert_eq!(report.constraints_before, 6);
+        assert_eq!(report.constraints_after, 2);
+        let witness = generate_witness(&optimized, &r1cs, &ints(&[12]), &ints(&[2, 3])).unwrap();
+        assert!(r1cs.is_satisfied(&witness));
+    }
+}
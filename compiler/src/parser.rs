@@ -0,0 +1,641 @@
+use num_traits::ToPrimitive;
+
+use crate::ast::{
+    BinaryOp, Expr, ExprKind, Function, Ident, Program, Span, Statement, StatementKind, Visibility,
+};
+use crate::error::CompileError;
+use crate::lexer::{tokenize, Token, TokenKind};
+
+/// Expressions and blocks nested inside each other the parser accepts, deeper source
+/// would overflow the stack here or in the passes walking the tree
+pub const MAX_NESTING: usize = 64;
+
+/// Parse circuit source into a `Program`
+///
+/// ```text
+/// public c;
+/// private a, b;
+/// let product = a * b;
+/// assert_eq(product + 1, c);
+/// output doubled = 2 * c;
+///
+/// fn square(x) { return x * x; }
+/// private xs[4];
+/// let mut sum = 0;
+/// for i in 0..4 { sum = sum + square(xs[i]); }
+///
+/// table bytes = range(8);
+/// lookup(bytes, sum);
+/// ```
+pub fn parse(source: &str) -> Result<Program, CompileError> {
+    let tokens = tokenize(source)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        depth: 0,
+    };
+    let mut program = Program::default();
+    while parser.peek().kind != TokenKind::Eof {
+        if parser.peek().kind == TokenKind::Fn {
+            program.functions.push(parser.function()?);
+        } else {
+            program.statements.append(&mut parser.statement()?);
+        }
+    }
+    Ok(program)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// expressions and blocks currently being parsed inside each other
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        // the trailing Eof token is never consumed
+        if token.kind != TokenKind::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn expect(&mut self, kind: TokenKind, what: &str) -> Result<Token, CompileError> {
+        if self.peek().kind == kind {
+            Ok(self.advance())
+        } else {
+            Err(self.unexpected(what))
+        }
+    }
+
+    fn unexpected(&self, what: &str) -> CompileError {
+        let token = self.peek();
+        CompileError::Parse {
+            message: format!("expected {}, found {}", what, token.kind),
+            span: token.span,
+        }
+    }
+
+    /// run `parse` one nesting level deeper
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, CompileError>,
+    ) -> Result<T, CompileError> {
+        if self.depth == MAX_NESTING {
+            return Err(CompileError::Parse {
+                message: format!("nesting deeper than {} levels", MAX_NESTING),
+                span: self.peek().span,
+            });
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn ident(&mut self) -> Result<Ident, CompileError> {
+        match self.peek().kind.clone() {
+            TokenKind::Ident(name) => {
+                let span = self.advance().span;
+                Ok(Ident { name, span })
+            }
+            _ => Err(self.unexpected("identifier")),
+        }
+    }
+
+    /// a declaration with several names expands to one statement per name
+    fn statement(&mut self) -> Result<Vec<Statement>, CompileError> {
+        let start = self.peek().span;
+        match self.peek().kind {
+            TokenKind::Public | TokenKind::Private => {
+                let visibility = if self.advance().kind == TokenKind::Public {
+                    Visibility::Public
+                } else {
+                    Visibility::Private
+                };
+                let mut names = vec![self.input()?];
+                while self.peek().kind == TokenKind::Comma {
+                    self.advance();
+                    names.push(self.input()?);
+                }
+                let end = self.expect(TokenKind::Semicolon, "`;`")?.span;
+                Ok(names
+                    .into_iter()
+                    .map(|(name, size)| Statement {
+                        kind: StatementKind::Input {
+                            visibility,
+                            name,
+                            size,
+                        },
+                        span: start.to(end),
+                    })
+                    .collect())
+            }
+            TokenKind::Let | TokenKind::Output => {
+                let is_output = self.advance().kind == TokenKind::Output;
+                let mutable = !is_output && self.peek().kind == TokenKind::Mut;
+                if mutable {
+                    self.advance();
+                }
+                let name = self.ident()?;
+                self.expect(TokenKind::Equals, "`=`")?;
+                let value = self.expr()?;
+                let end = self.expect(TokenKind::Semicolon, "`;`")?.span;
+                let kind = if is_output {
+                    StatementKind::Output { name, value }
+                } else {
+                    StatementKind::Let {
+                        name,
+                        mutable,
+                        value,
+                    }
+                };
+                Ok(vec![Statement {
+                    kind,
+                    span: start.to(end),
+                }])
+            }
+            TokenKind::AssertEq => {
+                self.advance();
+                self.expect(TokenKind::LParen, "`(`")?;
+                let lhs = self.expr()?;
+                self.expect(TokenKind::Comma, "`,`")?;
+                let rhs = self.expr()?;
+                self.expect(TokenKind::RParen, "`)`")?;
+                let end = self.expect(TokenKind::Semicolon, "`;`")?.span;
+                Ok(vec![Statement {
+                    kind: StatementKind::AssertEq { lhs, rhs },
+                    span: start.to(end),
+                }])
+            }
+            TokenKind::For => {
+                self.advance();
+                let var = self.ident()?;
+                self.expect(TokenKind::In, "`in`")?;
+                let range_start = self.expr()?;
+                self.expect(TokenKind::DotDot, "`..`")?;
+                let range_end = self.expr()?;
+                let (body, end) = self.block()?;
+                Ok(vec![Statement {
+                    kind: StatementKind::For {
+                        var,
+                        start: range_start,
+                        end: range_end,
+                        body,
+                    },
+                    span: start.to(end),
+                }])
+            }
+            TokenKind::Table => {
+                self.advance();
+                let name = self.ident()?;
+                self.expect(TokenKind::Equals, "`=`")?;
+                let value = self.expr()?;
+                let end = self.expect(TokenKind::Semicolon, "`;`")?.span;
+                Ok(vec![Statement {
+                    kind: StatementKind::Table { name, value },
+                    span: start.to(end),
+                }])
+            }
+            TokenKind::Lookup => {
+                self.advance();
+                self.expect(TokenKind::LParen, "`(`")?;
+                let table = self.ident()?;
+                let mut args = Vec::new();
+                while self.peek().kind == TokenKind::Comma {
+                    self.advance();
+                    args.push(self.expr()?);
+                }
+                self.expect(TokenKind::RParen, "`)`")?;
+                let end = self.expect(TokenKind::Semicolon, "`;`")?.span;
+                Ok(vec![Statement {
+                    kind: StatementKind::Lookup { table, args },
+                    span: start.to(end),
+                }])
+            }
+            TokenKind::Return => {
+                self.advance();
+                let value = self.expr()?;
+                let end = self.expect(TokenKind::Semicolon, "`;`")?.span;
+                Ok(vec![Statement {
+                    kind: StatementKind::Return { value },
+                    span: start.to(end),
+                }])
+            }
+            TokenKind::Ident(_) => {
+                let name = self.ident()?;
+                let index = if self.peek().kind == TokenKind::LBracket {
+                    self.advance();
+                    let index = self.expr()?;
+                    self.expect(TokenKind::RBracket, "`]`")?;
+                    Some(index)
+                } else {
+                    None
+                };
+                self.expect(TokenKind::Equals, "`=`")?;
+                let value = self.expr()?;
+                let end = self.expect(TokenKind::Semicolon, "`;`")?.span;
+                Ok(vec![Statement {
+                    kind: StatementKind::Assign { name, index, value },
+                    span: start.to(end),
+                }])
+            }
+            _ => Err(self.unexpected("statement")),
+        }
+    }
+
+    /// `name` or `name[length]` in an input declaration
+    fn input(&mut self) -> Result<(Ident, Option<usize>), CompileError> {
+        let name = self.ident()?;
+        if self.peek().kind != TokenKind::LBracket {
+            return Ok((name, None));
+        }
+        self.advance();
+        let size = match self.peek().kind.clone() {
+            TokenKind::Number(n) => n.to_usize(),
+            _ => None,
+        }
+        .ok_or_else(|| self.unexpected("array length"))?;
+        self.advance();
+        self.expect(TokenKind::RBracket, "`]`")?;
+        Ok((name, Some(size)))
+    }
+
+    // function := "fn" identifier "(" (identifier ("," identifier)*)? ")" block
+    fn function(&mut self) -> Result<Function, CompileError> {
+        let start = self.expect(TokenKind::Fn, "`fn`")?.span;
+        let name = self.ident()?;
+        self.expect(TokenKind::LParen, "`(`")?;
+        let mut params: Vec<Ident> = Vec::new();
+        if self.peek().kind != TokenKind::RParen {
+            params.push(self.ident()?);
+            while self.peek().kind == TokenKind::Comma {
+                self.advance();
+                params.push(self.ident()?);
+            }
+        }
+        self.expect(TokenKind::RParen, "`)`")?;
+        let (body, end) = self.block()?;
+        Ok(Function {
+            name,
+            params,
+            body,
+            span: start.to(end),
+        })
+    }
+
+    /// statements between braces and the span of the closing brace
+    fn block(&mut self) -> Result<(Vec<Statement>, Span), CompileError> {
+        self.expect(TokenKind::LBrace, "`{`")?;
+        self.nested(|parser| {
+            let mut statements: Vec<Statement> = Vec::new();
+            while parser.peek().kind != TokenKind::RBrace {
+                if parser.peek().kind == TokenKind::Eof {
+                    return Err(parser.unexpected("`}`"));
+                }
+                statements.append(&mut parser.statement()?);
+            }
+            let end = parser.advance().span;
+            Ok((statements, end))
+        })
+    }
+
+    // expr := term (("+" | "-") term)*
+    fn expr(&mut self) -> Result<Expr, CompileError> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Plus => BinaryOp::Add,
+                TokenKind::Minus => BinaryOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.advance();
+            let rhs = self.term()?;
+            lhs = binary(op, lhs, rhs);
+        }
+    }
+
+    // term := unary (("*" | "/") unary)*
+    fn term(&mut self) -> Result<Expr, CompileError> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Star => BinaryOp::Mul,
+                TokenKind::Slash => BinaryOp::Div,
+                _ => return Ok(lhs),
+            };
+            self.advance();
+            let rhs = self.unary()?;
+            lhs = binary(op, lhs, rhs);
+        }
+    }
+
+    // unary := "-" unary | postfix
+    //
+    // Every nested expression passes through here, so this is where depth is counted.
+    fn unary(&mut self) -> Result<Expr, CompileError> {
+        self.nested(|parser| {
+            if parser.peek().kind == TokenKind::Minus {
+                let start = parser.advance().span;
+                let inner = parser.unary()?;
+                return Ok(Expr {
+                    span: start.to(inner.span),
+                    kind: ExprKind::Neg(Box::new(inner)),
+                });
+            }
+            parser.postfix()
+        })
+    }
+
+    // postfix := primary ("[" expr "]")*
+    fn postfix(&mut self) -> Result<Expr, CompileError> {
+        let mut array = self.primary()?;
+        while self.peek().kind == TokenKind::LBracket {
+            self.advance();
+            let index = self.expr()?;
+            let end = self.expect(TokenKind::RBracket, "`]`")?.span;
+            array = Expr {
+                span: array.span.to(end),
+                kind: ExprKind::Index {
+                    array: Box::new(array),
+                    index: Box::new(index),
+                },
+            };
+        }
+        Ok(array)
+    }
+
+    // primary := number | identifier | call | array | "(" expr ")"
+    //          | "if" expr "then" expr "else" expr
+    // array := "[" (expr ("," expr)*)? "]" | "[" expr ";" expr "]"
+    // call := identifier "(" (expr ("," expr)*)? ")"
+    fn primary(&mut self) -> Result<Expr, CompileError> {
+        match self.peek().kind.clone() {
+            TokenKind::Number(n) => {
+                let span = self.advance().span;
+                Ok(Expr {
+                    kind: ExprKind::Constant(n),
+                    span,
+                })
+            }
+            TokenKind::Ident(_) => {
+                let ident = self.ident()?;
+                if self.peek().kind != TokenKind::LParen {
+                    return Ok(Expr {
+                        span: ident.span,
+                        kind: ExprKind::Variable(ident),
+                    });
+                }
+                self.advance();
+                let mut args: Vec<Expr> = Vec::new();
+                if self.peek().kind != TokenKind::RParen {
+                    args.push(self.expr()?);
+                    while self.peek().kind == TokenKind::Comma {
+                        self.advance();
+                        args.push(self.expr()?);
+                    }
+                }
+                let end = self.expect(TokenKind::RParen, "`)`")?.span;
+                Ok(Expr {
+                    span: ident.span.to(end),
+                    kind: ExprKind::Call { name: ident, args },
+                })
+            }
+            TokenKind::LParen => {
+                let start = self.advance().span;
+                let mut inner = self.expr()?;
+                let end = self.expect(TokenKind::RParen, "`)`")?.span;
+                inner.span = start.to(end);
+                Ok(inner)
+            }
+            TokenKind::LBracket => {
+                let start = self.advance().span;
+                let mut elements: Vec<Expr> = Vec::new();
+                if self.peek().kind != TokenKind::RBracket {
+                    elements.push(self.expr()?);
+                    if self.peek().kind == TokenKind::Semicolon {
+                        self.advance();
+                        let count = self.expr()?;
+                        let end = self.expect(TokenKind::RBracket, "`]`")?.span;
+                        return Ok(Expr {
+                            span: start.to(end),
+                            kind: ExprKind::Repeat {
+                                value: Box::new(elements.remove(0)),
+                                count: Box::new(count),
+                            },
+                        });
+                    }
+                    while self.peek().kind == TokenKind::Comma {
+                        self.advance();
+                        elements.push(self.expr()?);
+                    }
+                }
+                let end = self.expect(TokenKind::RBracket, "`]`")?.span;
+                Ok(Expr {
+                    span: start.to(end),
+                    kind: ExprKind::Array(elements),
+                })
+            }
+            // the branches extend as far right as possible, like a `let` value
+            TokenKind::If => {
+                let start = self.advance().span;
+                let cond = self.expr()?;
+                self.expect(TokenKind::Then, "`then`")?;
+                let then_branch = self.expr()?;
+                self.expect(TokenKind::Else, "`else`")?;
+                let else_branch = self.expr()?;
+                Ok(Expr {
+                    span: start.to(else_branch.span),
+                    kind: ExprKind::If {
+                        cond: Box::new(cond),
+                        then_branch: Box::new(then_branch),
+                        else_branch: Box::new(else_branch),
+                    },
+                })
+            }
+            _ => Err(self.unexpected("expression")),
+        }
+    }
+}
+
+fn binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Expr {
+    Expr {
+        span: lhs.span.to(rhs.span),
+        kind: ExprKind::Binary {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, MAX_NESTING};
+    use crate::ast::{BinaryOp, Expr, ExprKind, Span, StatementKind, Visibility};
+    use crate::error::CompileError;
+
+    #[test]
+    fn parse_addition_program() {
+        let source = "public c;\nprivate a, b;\nlet sum = a + b;\nassert_eq(sum, c);\n";
+        let program = parse(source).unwrap();
+        assert_eq!(program.statements.len(), 5);
+        assert_eq!(program.inputs(Visibility::Public)[0].name, "c");
+        assert_eq!(program.inputs(Visibility::Private).len(), 2);
+        match &program.statements[3].kind {
+            StatementKind::Let { name, value, .. } => {
+                assert_eq!(name.name, "sum");
+                assert_eq!(&source[value.span.start..value.span.end], "a + b");
+            }
+            other => panic!("expected let, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn multiplication_binds_tighter() {
+        let program = parse("let x = 1 + 2 * -3;").unwrap();
+        let StatementKind::Let { value, .. } = &program.statements[0].kind else {
+            panic!("expected let");
+        };
+        let ExprKind::Binary { op, rhs, .. } = &value.kind else {
+            panic!("expected binary expression");
+        };
+        assert_eq!(*op, BinaryOp::Add);
+        assert!(matches!(
+            rhs.kind,
+            ExprKind::Binary {
+                op: BinaryOp::Mul,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn missing_semicolon() {
+        let err = parse("let x = a\nlet y = b;").unwrap_err();
+        assert_eq!(err.span(), Some(Span::new(10, 13)));
+        assert_eq!(err.to_string(), "expected `;`, found `let`");
+    }
+
+    #[test]
+    fn nesting_limit() {
+        let nested = |depth: usize| format!("let x = {}1{};", "(".repeat(depth), ")".repeat(depth));
+        // the outermost expression is one level, each parenthesis adds another
+        assert!(parse(&nested(MAX_NESTING - 1)).is_ok());
+        let err = parse(&nested(MAX_NESTING)).unwrap_err();
+        assert_eq!(
+            err.span(),
+            Some(Span::new(8 + MAX_NESTING, 9 + MAX_NESTING))
+        );
+        assert_eq!(err.to_string(), "nesting deeper than 64 levels");
+        // deep enough to overflow the stack without the limit
+        for source in [
+            format!("let x = {}1;", "-".repeat(100_000)),
+            format!("let x = {}1;", "[".repeat(100_000)),
+            "for i in 0..1 {".repeat(100_000),
+        ] {
+            assert!(matches!(parse(&source), Err(CompileError::Parse { .. })));
+        }
+    }
+
+    #[test]
+    fn tables_and_lookups() {
+        let program = parse("table t = xor(2);\nlookup(t, a, b, a + b);").unwrap();
+        assert!(matches!(
+            &program.statements[0].kind,
+            StatementKind::Table { name, value: Expr { kind: ExprKind::Call { .. }, .. } }
+                if name.name == "t"
+        ));
+        assert!(matches!(
+            &program.statements[1].kind,
+            StatementKind::Lookup { table, args } if table.name == "t" && args.len() == 3
+        ));
+        let err = parse("lookup(1, x);").unwrap_err();
+        assert_eq!(err.to_string(), "expected identifier, found `1`");
+    }
+
+    #[test]
+    fn division_and_calls() {
+        let program = parse("let x = a / sqrt(b, 2) * inv();").unwrap();
+        let StatementKind::Let { value, .. } = &program.statements[0].kind else {
+            panic!("expected let");
+        };
+        let ExprKind::Binary { op, lhs, rhs } = &value.kind else {
+            panic!("expected binary expression");
+        };
+        assert_eq!(*op, BinaryOp::Mul);
+        assert!(
+            matches!(&rhs.kind, ExprKind::Call { name, args } if name.name == "inv" && args.is_empty())
+        );
+        let ExprKind::Binary { op, rhs, .. } = &lhs.kind else {
+            panic!("expected binary expression");
+        };
+        assert_eq!(*op, BinaryOp::Div);
+        assert!(matches!(&rhs.kind, ExprKind::Call { args, .. } if args.len() == 2));
+    }
+
+    #[test]
+    fn functions_loops_and_arrays() {
+        let source = "fn dot(a, b) {\n  let mut acc = 0;\n  for i in 0..2 { acc = acc + a[i] * b[i]; }\n  return acc;\n}\nprivate xs[2], y;\nlet ys = [y; 2];\noutput d = dot(xs, [1, ys[1]]);";
+        let program = parse(source).unwrap();
+        assert_eq!(program.functions.len(), 1);
+        let function = &program.functions[0];
+        assert_eq!(function.params.len(), 2);
+        assert!(matches!(
+            function.body[0].kind,
+            StatementKind::Let { mutable: true, .. }
+        ));
+        let StatementKind::For { body, .. } = &function.body[1].kind else {
+            panic!("expected for loop");
+        };
+        assert!(matches!(
+            body[0].kind,
+            StatementKind::Assign { index: None, .. }
+        ));
+        assert!(matches!(
+            function.body[2].kind,
+            StatementKind::Return { .. }
+        ));
+        assert!(matches!(
+            program.statements[0].kind,
+            StatementKind::Input { size: Some(2), .. }
+        ));
+        assert!(matches!(
+            program.statements[1].kind,
+            StatementKind::Input { size: None, .. }
+        ));
+        let StatementKind::Let { value, .. } = &program.statements[2].kind else {
+            panic!("expected let");
+        };
+        assert!(matches!(value.kind, ExprKind::Repeat { .. }));
+
+        let err = parse("private xs[n];").unwrap_err();
+        assert_eq!(err.to_string(), "expected array length, found `n`");
+    }
+
+    #[test]
+    fn conditional_expression() {
+        let source = "let x = 2 * if c then a + 1 else b;";
+        let program = parse(source).unwrap();
+        let StatementKind::Let { value, .. } = &program.statements[0].kind else {
+            panic!("expected let");
+        };
+        let ExprKind::Binary { rhs, .. } = &value.kind else {
+            panic!("expected binary expression");
+        };
+        assert!(matches!(rhs.kind, ExprKind::If { .. }));
+        assert_eq!(
+            &source[rhs.span.start..rhs.span.end],
+            "if c then a + 1 else b"
+        );
+
+        let err = parse("let x = if c then a;").unwrap_err();
+        assert_eq!(err.to_string(), "expected `else`, found `;`");
+    }
+}
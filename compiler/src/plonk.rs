@@ -0,0 +1,527 @@
+use std::collections::HashMap;
+
+use num_bigint::BigInt;
+use num_traits::{One, Zero};
+use rust_ecc::field::{FqElement, FQ};
+
+use crate::ast::Visibility;
+use crate::circuit::{Circuit, GateKind, Operand, Wire};
+use crate::error::CompileError;
+use crate::r1cs::{check_modulus, fq};
+
+pub mod custom;
+pub mod lookup;
+
+pub use custom::CustomGate;
+pub use lookup::{LookupTable, PlookupProof};
+
+/// Selector values of one row, `q_L·a + q_R·b + q_O·c + q_M·a·b + q_C = 0`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Selectors {
+    pub q_l: FQ,
+    pub q_r: FQ,
+    pub q_o: FQ,
+    pub q_m: FQ,
+    pub q_c: FQ,
+}
+
+/// PLONKish gate table
+///
+/// Every row has three wire cells `a`, `b` and `c` holding circuit wires, cells a
+/// row does not use are `None`. The first `num_public` rows carry the outputs and
+/// public inputs in R1CS order with only `q_L = 1`, their values enter through the
+/// public input term `PI_i = -x_i`. Cells holding the same wire are tied together by
+/// the copy permutation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlonkCircuit {
+    pub field_modulus: BigInt,
+    pub num_public: usize,
+    pub selectors: Vec<Selectors>,
+    pub a: Vec<Option<Wire>>,
+    pub b: Vec<Option<Wire>>,
+    pub c: Vec<Option<Wire>>,
+    /// index of the gate each row was lowered from, `None` for public input rows
+    pub row_gates: Vec<Option<usize>>,
+    pub custom_gates: Vec<CustomGate>,
+    /// custom gate starting in each row, its selector is one there and zero elsewhere
+    pub custom: Vec<Option<usize>>,
+    pub tables: Vec<LookupTable>,
+    /// table the cells of each row have to appear in, the lookup selector
+    pub lookup: Vec<Option<usize>>,
+}
+
+/// Wire columns filled in with values, the PLONK witness
+pub type Columns = [Vec<FQ>; 3];
+
+impl PlonkCircuit {
+    /// One row per addition, multiplication, assertion and lookup, constant operands
+    /// are folded into the selectors and hints only need their wire
+    ///
+    /// Fails on a modulus below two or a constant that does not fit in the field.
+    pub fn from_circuit(
+        circuit: &Circuit,
+        field_modulus: impl Into<BigInt>,
+    ) -> Result<PlonkCircuit, CompileError> {
+        PlonkCircuit::with_custom_gates(circuit, field_modulus, Vec::new())
+    }
+
+    /// Like `from_circuit`, but a run of gates matching a custom gate's pattern is
+    /// replaced by that gate, trying `custom_gates` in order
+    pub fn with_custom_gates(
+        circuit: &Circuit,
+        field_modulus: impl Into<BigInt>,
+        custom_gates: Vec<CustomGate>,
+    ) -> Result<PlonkCircuit, CompileError> {
+        let field_modulus = field_modulus.into();
+        check_modulus(&field_modulus)?;
+        circuit.check_constants(&field_modulus)?;
+        let p = &field_modulus;
+        let mut plonk = PlonkCircuit {
+            field_modulus: field_modulus.clone(),
+            num_public: 0,
+            selectors: Vec::new(),
+            a: Vec::new(),
+            b: Vec::new(),
+            c: Vec::new(),
+            row_gates: Vec::new(),
+            custom_gates,
+            custom: Vec::new(),
+            tables: circuit.tables.clone(),
+            lookup: Vec::new(),
+        };
+        let public = circuit
+            .outputs()
+            .into_iter()
+            .chain(circuit.inputs(Visibility::Public));
+        for wire in public {
+            let mut selectors = Selectors::zero(p);
+            selectors.q_l = fq(BigInt::one(), p);
+            plonk.push(selectors, [Some(wire), None, None], None, None);
+            plonk.num_public += 1;
+        }
+
+        let mut reads = vec![0; circuit.wires.len()];
+        for gate in &circuit.gates {
+            let wires: Vec<Wire> = match &gate.kind {
+                GateKind::Add { lhs, rhs, .. }
+                | GateKind::Mul { lhs, rhs, .. }
+                | GateKind::AssertEq { lhs, rhs } => {
+                    [lhs, rhs].into_iter().filter_map(Operand::wire).collect()
+                }
+                GateKind::Hint { args, .. } => args.iter().filter_map(Operand::wire).collect(),
+                GateKind::Lookup { args, .. } => args.clone(),
+            };
+            for wire in wires {
+                reads[wire.0] += 1;
+            }
+        }
+
+        let one = BigInt::one();
+        let mut index = 0;
+        'gates: while index < circuit.gates.len() {
+            for (k, custom) in plonk.custom_gates.iter().enumerate() {
+                if let Some((len, cells)) = custom.matches(circuit, index, &reads) {
+                    for (row, cells) in cells.chunks(3).enumerate() {
+                        let cells = [cells[0], cells[1], cells[2]];
+                        let custom = (row == 0).then_some(k);
+                        plonk.push(Selectors::zero(p), cells, Some(index), custom);
+                    }
+                    index += len;
+                    continue 'gates;
+                }
+            }
+            let gate = &circuit.gates[index];
+            index += 1;
+            let mut s = Selectors::zero(p);
+            let cells = match &gate.kind {
+                GateKind::Add { lhs, rhs, out } => {
+                    s.q_o = fq(-&one, p);
+                    let a = s.linear(lhs, &one, Side::Left);
+                    let b = s.linear(rhs, &one, Side::Right);
+                    [a, b, Some(*out)]
+                }
+                GateKind::Mul { lhs, rhs, out } => {
+                    s.q_o = fq(-&one, p);
+                    match (lhs, rhs) {
+                        (Operand::Wire(l), Operand::Wire(r)) => {
+                            s.q_m = fq(one.clone(), p);
+                            [Some(*l), Some(*r), Some(*out)]
+                        }
+                        (Operand::Constant(k), wire) | (wire, Operand::Constant(k)) => {
+                            let a = s.linear(wire, k, Side::Left);
+                            [a, None, Some(*out)]
+                        }
+                    }
+                }
+                GateKind::AssertEq { lhs, rhs } => {
+                    let a = s.linear(lhs, &one, Side::Left);
+                    let b = s.linear(rhs, &-&one, Side::Right);
+                    [a, b, None]
+                }
+                GateKind::Hint { .. } => continue,
+                GateKind::Lookup { table, args } => {
+                    plonk.lookup_row(*table, args, Some(index - 1));
+                    continue;
+                }
+            };
+            plonk.push(s, cells, Some(index - 1), None);
+        }
+        Ok(plonk)
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.selectors.len()
+    }
+
+    fn push(
+        &mut self,
+        selectors: Selectors,
+        cells: [Option<Wire>; 3],
+        gate: Option<usize>,
+        custom: Option<usize>,
+    ) {
+        self.selectors.push(selectors);
+        self.custom.push(custom);
+        self.lookup.push(None);
+        self.a.push(cells[0]);
+        self.b.push(cells[1]);
+        self.c.push(cells[2]);
+        self.row_gates.push(gate);
+    }
+
+    /// register a fixed table, returning its index for `lookup`
+    pub fn add_table(&mut self, table: LookupTable) -> usize {
+        self.tables.push(table);
+        self.tables.len() - 1
+    }
+
+    /// Append a row requiring the values of `wires` to form an entry of `table`
+    ///
+    /// The wires fill the `a`, `b`, `c` cells in order and are tied to the rest of the
+    /// table by the copy permutation like any other cell.
+    pub fn lookup(&mut self, table: usize, wires: &[Wire]) {
+        self.lookup_row(table, wires, None);
+    }
+
+    fn lookup_row(&mut self, table: usize, wires: &[Wire], gate: Option<usize>) {
+        assert_eq!(
+            wires.len(),
+            self.tables[table].width(),
+            "lookup width does not match table `{}`",
+            self.tables[table].name
+        );
+        let mut cells = [None; 3];
+        for (cell, wire) in cells.iter_mut().zip(wires) {
+            *cell = Some(*wire);
+        }
+        let zero = Selectors::zero(&self.field_modulus);
+        self.push(zero, cells, gate, None);
+        *self.lookup.last_mut().unwrap() = Some(table);
+    }
+
+    /// compressed cell tuples of the rows looking up `table`, the plookup queries
+    fn queries(&self, table: usize, columns: &Columns, alpha: &FQ) -> Vec<FQ> {
+        let width = self.tables[table].width();
+        (0..self.num_rows())
+            .filter(|row| self.lookup[*row] == Some(table))
+            .map(|row| {
+                let values: Vec<FQ> = columns[..width].iter().map(|c| c[row].clone()).collect();
+                lookup::compress(&values, alpha)
+            })
+            .collect()
+    }
+
+    /// One plookup proof per table, `None` if some lookup row is not in its table
+    ///
+    /// `alpha` compresses tuples, `beta` and `gamma` are the plookup challenges; a
+    /// real prover derives all three from a transcript of the committed columns.
+    pub fn prove_lookups(
+        &self,
+        columns: &Columns,
+        alpha: &FQ,
+        beta: &FQ,
+        gamma: &FQ,
+    ) -> Option<Vec<PlookupProof>> {
+        (0..self.tables.len())
+            .map(|k| {
+                let table = self.tables[k].compressed(alpha);
+                lookup::prove(&self.queries(k, columns, alpha), &table, beta, gamma)
+            })
+            .collect()
+    }
+
+    pub fn verify_lookups(
+        &self,
+        columns: &Columns,
+        proofs: &[PlookupProof],
+        alpha: &FQ,
+        beta: &FQ,
+        gamma: &FQ,
+    ) -> bool {
+        proofs.len() == self.tables.len()
+            && proofs.iter().enumerate().all(|(k, proof)| {
+                let table = self.tables[k].compressed(alpha);
+                let queries = self.queries(k, columns, alpha);
+                lookup::verify(&queries, &table, proof, beta, gamma)
+            })
+    }
+
+    fn cell(&self, index: usize) -> Option<Wire> {
+        let n = self.num_rows();
+        [&self.a, &self.b, &self.c][index / n][index % n]
+    }
+
+    /// Copy permutation σ over the `3n` cells, cell `column * n + row` with columns
+    /// `a`, `b`, `c` in that order
+    ///
+    /// Cells holding the same wire form one cycle in row order, empty cells map to
+    /// themselves.
+    pub fn permutation(&self) -> Vec<usize> {
+        let cells = 3 * self.num_rows();
+        let mut sigma: Vec<usize> = (0..cells).collect();
+        let mut first: HashMap<Wire, usize> = HashMap::new();
+        let mut last: HashMap<Wire, usize> = HashMap::new();
+        for index in 0..cells {
+            if let Some(wire) = self.cell(index) {
+                if let Some(previous) = last.insert(wire, index) {
+                    sigma[previous] = index;
+                }
+                first.entry(wire).or_insert(index);
+            }
+        }
+        for (wire, end) in last {
+            sigma[end] = first[&wire];
+        }
+        sigma
+    }
+
+    /// cell values from wire values, e.g. from `wire_values`, empty cells are zero
+    pub fn assign(&self, wires: &[Option<FQ>]) -> Columns {
+        let zero = fq(BigInt::zero(), &self.field_modulus);
+        [&self.a, &self.b, &self.c].map(|column| {
+            column
+                .iter()
+                .map(|cell| match cell {
+                    Some(wire) => wires[wire.0]
+                        .clone()
+                        .expect("rows only refer to computed wires"),
+                    None => zero.clone(),
+                })
+                .collect()
+        })
+    }
+
+    /// check the gate equation of one row, a custom gate starting in it and its
+    /// lookup, `public` holds outputs then public inputs
+    pub fn is_row_satisfied(&self, row: usize, columns: &Columns, public: &[FQ]) -> bool {
+        let s = &self.selectors[row];
+        let [a, b, c] = [&columns[0][row], &columns[1][row], &columns[2][row]];
+        let term = |q: &FQ, x: &FQ| q.mul(FqElement::FQ(x.clone()));
+        let mut sum = term(&s.q_l, a)
+            .add(FqElement::FQ(term(&s.q_r, b)))
+            .add(FqElement::FQ(term(&s.q_o, c)))
+            .add(FqElement::FQ(term(&s.q_m, a).mul(FqElement::FQ(b.clone()))))
+            .add(FqElement::FQ(s.q_c.clone()));
+        if row < self.num_public {
+            sum = sum.sub(FqElement::FQ(public[row].clone()));
+        }
+        let custom = self.custom[row].is_none_or(|k| {
+            let gate = &self.custom_gates[k];
+            let cells: Vec<FQ> = (row..row + gate.rows())
+                .flat_map(|r| columns.iter().map(move |column| column[r].clone()))
+                .collect();
+            gate.evaluate(&cells).iter().all(|v| v.n.is_zero())
+        });
+        let lookup = self.lookup[row].is_none_or(|k| {
+            let table = &self.tables[k];
+            let values: Vec<FQ> = columns[..table.width()]
+                .iter()
+                .map(|column| column[row].clone())
+                .collect();
+            table.contains(&values)
+        });
+        sum.n.is_zero() && custom && lookup
+    }
+
+    /// index of the first row whose gate equation fails
+    pub fn first_unsatisfied(&self, columns: &Columns, public: &[FQ]) -> Option<usize> {
+        (0..self.num_rows()).find(|row| !self.is_row_satisfied(*row, columns, public))
+    }
+
+    /// every cell equals the cell the permutation sends it to
+    pub fn copies_satisfied(&self, columns: &Columns) -> bool {
+        let n = self.num_rows();
+        let value = |index: usize| &columns[index / n][index % n];
+        self.permutation()
+            .into_iter()
+            .enumerate()
+            .all(|(from, to)| value(from) == value(to))
+    }
+
+    pub fn is_satisfied(&self, columns: &Columns, public: &[FQ]) -> bool {
+        public.len() == self.num_public
+            && columns.iter().all(|column| column.len() == self.num_rows())
+            && self.first_unsatisfied(columns, public).is_none()
+            && self.copies_satisfied(columns)
+    }
+}
+
+enum Side {
+    Left,
+    Right,
+}
+
+impl Selectors {
+    fn zero(p: &BigInt) -> Selectors {
+        let zero = fq(BigInt::zero(), p);
+        Selectors {
+            q_l: zero.clone(),
+            q_r: zero.clone(),
+            q_o: zero.clone(),
+            q_m: zero.clone(),
+            q_c: zero,
+        }
+    }
+
+    /// add `k * operand` to the row, a wire goes into the `a` or `b` cell and a
+    /// constant into `q_C`
+    fn linear(&mut self, operand: &Operand, k: &BigInt, side: Side) -> Option<Wire> {
+        let p = self.q_c.field_modulus.clone();
+        match operand {
+            Operand::Wire(wire) => {
+                let q = match side {
+                    Side::Left => &mut self.q_l,
+                    Side::Right => &mut self.q_r,
+                };
+                *q = fq(k.clone(), &p);
+                Some(*wire)
+            }
+            Operand::Constant(n) => {
+                self.q_c = self.q_c.add(FqElement::FQ(fq(k * n, &p)));
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use super::{LookupTable, PlonkCircuit};
+    use crate::circuit::Wire;
+    use crate::r1cs::fq;
+    use crate::witness::wire_values;
+    use crate::{evaluate, flatten, parse, Field, R1CS};
+
+    #[test]
+    fn satisfied_by_dsl_witness() {
+        let source = "public y;\nprivate x;\nlet z = x * x * x + x + 5;\nassert_eq(z, y);\noutput o = 2 * z - 1;";
+        let circuit = flatten(&parse(source).unwrap()).unwrap();
+        let p = BigInt::from(1009);
+        let plonk = PlonkCircuit::from_circuit(&circuit, p.clone()).unwrap();
+        // two public rows, then one row per gate
+        assert_eq!(plonk.num_public, 2);
+        assert_eq!(plonk.num_rows(), 2 + circuit.gates.len());
+
+        let wires = wire_values(&circuit, &p, &[35.into()], &[3.into()]).unwrap();
+        let mut columns = plonk.assign(&wires);
+        let public = [69, 35].map(|v| fq(BigInt::from(v), &p));
+        assert!(plonk.is_satisfied(&columns, &public));
+        assert_eq!(
+            plonk.first_unsatisfied(&columns, &[69, 36].map(|v| fq(v.into(), &p))),
+            Some(1)
+        );
+
+        // changing one copy of `x` breaks the permutation but not the row it sits in
+        let x_cell = plonk
+            .a
+            .iter()
+            .position(|w| w.map(|w| w.0) == Some(1))
+            .unwrap();
+        columns[0][x_cell] = fq(4.into(), &p);
+        assert!(!plonk.copies_satisfied(&columns));
+    }
+
+    #[test]
+    fn lookup_rows() {
+        let circuit = flatten(&parse("private a, b, c;\noutput s = a + b + c;").unwrap()).unwrap();
+        let p = Field::Bn128.modulus();
+        let mut plonk = PlonkCircuit::from_circuit(&circuit, p.clone()).unwrap();
+        let xor = plonk.add_table(LookupTable::xor(4));
+        let range = plonk.add_table(LookupTable::range(8));
+        let [a, b, c] = [0, 1, 2].map(Wire);
+        plonk.lookup(xor, &[a, b, c]);
+        plonk.lookup(range, &[a]);
+        let challenges = [7, 11, 13].map(|v| fq(BigInt::from(v), &p));
+        let [alpha, beta, gamma] = &challenges;
+
+        for (inputs, ok) in [([12, 10, 6], true), ([12, 10, 7], false)] {
+            let inputs = inputs.map(BigInt::from);
+            let wires = wire_values(&circuit, &p, &[], &inputs).unwrap();
+            let columns = plonk.assign(&wires);
+            let public = [columns[2][2].clone()];
+            assert_eq!(plonk.is_satisfied(&columns, &public), ok);
+            let proofs = plonk.prove_lookups(&columns, alpha, beta, gamma);
+            assert_eq!(proofs.is_some(), ok);
+            if let Some(proofs) = proofs {
+                assert!(plonk.verify_lookups(&columns, &proofs, alpha, beta, gamma));
+            }
+        }
+    }
+
+    #[test]
+    fn lookups_from_source() {
+        let source = "table bytes = range(8);\ntable nibbles = xor(4);\nprivate a, b, c;\nlookup(nibbles, a, b, c);\nlookup(bytes, a * 16 + b);\noutput s = a + b + c;";
+        let program = parse(source).unwrap();
+        let circuit = flatten(&program).unwrap();
+        let p = Field::Bn128.modulus();
+        let plonk = PlonkCircuit::from_circuit(&circuit, p.clone()).unwrap();
+        let rows: Vec<usize> = (0..plonk.num_rows())
+            .filter(|row| plonk.lookup[*row].is_some())
+            .collect();
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|row| plonk.row_gates[*row].is_some()));
+        let err = R1CS::from_circuit(&circuit, p.clone()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "R1CS has no lookup argument, lookups need the PLONK backend"
+        );
+
+        let challenges = [7, 11, 13].map(|v| fq(BigInt::from(v), &p));
+        let [alpha, beta, gamma] = &challenges;
+        for (inputs, ok) in [
+            ([12, 10, 6], true),
+            ([12, 10, 7], false),
+            ([16, 0, 16], false),
+        ] {
+            let inputs = inputs.map(BigInt::from);
+            let evaluated = evaluate(&program, &[], &inputs, p.clone());
+            assert_eq!(evaluated.is_ok(), ok);
+            let wires = wire_values(&circuit, &p, &[], &inputs).unwrap();
+            let columns = plonk.assign(&wires);
+            let public = [columns[0][0].clone()];
+            assert_eq!(plonk.is_satisfied(&columns, &public), ok);
+            let proofs = plonk.prove_lookups(&columns, alpha, beta, gamma);
+            assert_eq!(proofs.is_some(), ok);
+            if let Some(proofs) = proofs {
+                assert!(plonk.verify_lookups(&columns, &proofs, alpha, beta, gamma));
+            }
+        }
+    }
+
+    #[test]
+    fn permutation_cycles_through_copies() {
+        let circuit = flatten(&parse("private x;\noutput y = x * x + x;").unwrap()).unwrap();
+        let plonk = PlonkCircuit::from_circuit(&circuit, BigInt::from(101)).unwrap();
+        // rows: y public, x * x, w + x
+        let n = plonk.num_rows();
+        assert_eq!(n, 3);
+        let sigma = plonk.permutation();
+        // x sits in a1, b1 and b2: a1 -> b1 -> b2 -> a1
+        let (a1, b1, b2) = (1, n + 1, n + 2);
+        assert_eq!((sigma[a1], sigma[b1], sigma[b2]), (b1, b2, a1));
+        // the empty b0 cell is a fixed point
+        assert_eq!(sigma[n], n);
+    }
+}
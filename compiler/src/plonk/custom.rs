@@ -0,0 +1,398 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use num_bigint::BigInt;
+use rust_ecc::field::{FqElement, FQ};
+
+use crate::circuit::{Circuit, GateKind, Hint, Operand, Wire, WireKind};
+
+/// Polynomials over a custom gate's cells that vanish when the gate holds
+pub type Equations = fn(&[FQ]) -> Vec<FQ>;
+
+/// A row type beyond `q_L·a + q_R·b + q_O·c + q_M·a·b + q_C`, with its own selector
+///
+/// The gate replaces a run of consecutive gates matching one of its patterns. Its
+/// cells take up `a`, `b`, `c` of as many rows as needed, the equations can read all
+/// of them, which is how a gate spans rows. Wires a pattern computes but does not put
+/// in a cell vanish from the table, so a pattern only matches when no gate outside
+/// the run reads them.
+#[derive(Clone)]
+pub struct CustomGate {
+    pub name: &'static str,
+    pub degree: usize,
+    patterns: Vec<Vec<PatternGate>>,
+    cells: Vec<Option<String>>,
+    equations: Equations,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Term {
+    Name(String),
+    Constant(BigInt),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PatternGate {
+    Add(Term, Term, String),
+    Mul(Term, Term, String),
+    AssertEq(Term, Term),
+    Hint(Hint, Vec<Term>, String),
+}
+
+impl CustomGate {
+    /// Define a gate from patterns written the way `Circuit` is displayed
+    ///
+    /// ```text
+    /// t = x * x
+    /// y = t * -1
+    /// assert y == z
+    /// r <- div(y, x)
+    /// ```
+    ///
+    /// Names bind to wires, numbers only match equal constants. `cells` lists the
+    /// names placed in the cells row by row, `_` leaves a cell empty; every pattern has
+    /// to bind all of them. Panics on a malformed pattern.
+    pub fn new(
+        name: &'static str,
+        degree: usize,
+        patterns: &[&str],
+        cells: &[&str],
+        equations: Equations,
+    ) -> CustomGate {
+        let patterns: Vec<Vec<PatternGate>> = patterns.iter().map(|p| parse_pattern(p)).collect();
+        let cells: Vec<Option<String>> = cells
+            .iter()
+            .map(|c| (*c != "_").then(|| c.to_string()))
+            .collect();
+        for pattern in &patterns {
+            let names = bound_names(pattern);
+            for cell in cells.iter().flatten() {
+                assert!(
+                    names.contains(cell.as_str()),
+                    "cell `{}` of gate `{}` is not bound by every pattern",
+                    cell,
+                    name
+                );
+            }
+        }
+        CustomGate {
+            name,
+            degree,
+            patterns,
+            cells,
+            equations,
+        }
+    }
+
+    /// number of table rows the gate occupies
+    pub fn rows(&self) -> usize {
+        self.cells.len().div_ceil(3)
+    }
+
+    /// equation values for the cells of the gate's rows, all zero when it holds
+    pub fn evaluate(&self, cells: &[FQ]) -> Vec<FQ> {
+        (self.equations)(&cells[..self.cells.len()])
+    }
+
+    /// Number of gates matched at `start` and the wires of the cells, padded to whole rows
+    ///
+    /// `reads` counts how often every wire is read in the whole circuit.
+    pub(crate) fn matches(
+        &self,
+        circuit: &Circuit,
+        start: usize,
+        reads: &[usize],
+    ) -> Option<(usize, Vec<Option<Wire>>)> {
+        self.patterns.iter().find_map(|pattern| {
+            let gates = circuit.gates.get(start..start + pattern.len())?;
+            let mut bindings: HashMap<&str, Wire> = HashMap::new();
+            let mut produced: Vec<&str> = Vec::new();
+            let mut window_reads: HashMap<Wire, usize> = HashMap::new();
+            for (expected, gate) in pattern.iter().zip(gates) {
+                let (terms, operands, out): (Vec<&Term>, Vec<&Operand>, _) =
+                    match (expected, &gate.kind) {
+                        (PatternGate::Add(l, r, o), GateKind::Add { lhs, rhs, out })
+                        | (PatternGate::Mul(l, r, o), GateKind::Mul { lhs, rhs, out }) => {
+                            (vec![l, r], vec![lhs, rhs], Some((o, out)))
+                        }
+                        (PatternGate::AssertEq(l, r), GateKind::AssertEq { lhs, rhs }) => {
+                            (vec![l, r], vec![lhs, rhs], None)
+                        }
+                        (PatternGate::Hint(h, ts, o), GateKind::Hint { hint, args, out })
+                            if h == hint && ts.len() == args.len() =>
+                        {
+                            (ts.iter().collect(), args.iter().collect(), Some((o, out)))
+                        }
+                        _ => return None,
+                    };
+                for (term, operand) in terms.into_iter().zip(operands) {
+                    match (term, operand) {
+                        (Term::Constant(k), Operand::Constant(n)) if k == n => {}
+                        (Term::Name(name), Operand::Wire(wire)) => {
+                            if *bindings.entry(name).or_insert(*wire) != *wire {
+                                return None;
+                            }
+                            *window_reads.entry(*wire).or_default() += 1;
+                        }
+                        _ => return None,
+                    }
+                }
+                if let Some((name, wire)) = out {
+                    if bindings.insert(name, *wire).is_some() {
+                        return None;
+                    }
+                    produced.push(name);
+                }
+            }
+
+            let exposed: HashSet<&str> = self.cells.iter().flatten().map(|c| c.as_str()).collect();
+            for name in produced.iter().filter(|n| !exposed.contains(*n)) {
+                let wire = bindings[name];
+                let hidden = circuit.wires[wire.0].kind == WireKind::Internal
+                    && window_reads.get(&wire).copied().unwrap_or(0) == reads[wire.0];
+                if !hidden {
+                    return None;
+                }
+            }
+            let mut cells: Vec<Option<Wire>> = self
+                .cells
+                .iter()
+                .map(|c| c.as_ref().map(|name| bindings[name.as_str()]))
+                .collect();
+            cells.resize(3 * self.rows(), None);
+            Some((pattern.len(), cells))
+        })
+    }
+}
+
+impl fmt::Debug for CustomGate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CustomGate")
+            .field("name", &self.name)
+            .field("degree", &self.degree)
+            .field("cells", &self.cells)
+            .finish()
+    }
+}
+
+impl PartialEq for CustomGate {
+    fn eq(&self, other: &CustomGate) -> bool {
+        self.name == other.name
+            && self.degree == other.degree
+            && self.patterns == other.patterns
+            && self.cells == other.cells
+    }
+}
+
+impl Eq for CustomGate {}
+
+fn term(s: &str) -> Term {
+    match s.parse::<BigInt>() {
+        Ok(n) => Term::Constant(n),
+        Err(_) => Term::Name(s.to_string()),
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Vec<PatternGate> {
+    pattern
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let words: Vec<&str> = line
+                .split(|c: char| c.is_whitespace() || "(),".contains(c))
+                .filter(|w| !w.is_empty())
+                .collect();
+            match words.as_slice() {
+                [out, "=", l, "+", r] => PatternGate::Add(term(l), term(r), out.to_string()),
+                [out, "=", l, "*", r] => PatternGate::Mul(term(l), term(r), out.to_string()),
+                ["assert", l, "==", r] => PatternGate::AssertEq(term(l), term(r)),
+                [out, "<-", hint, args @ ..] => {
+                    let hint = match *hint {
+                        "div" => Hint::Div,
+                        name => {
+                            Hint::builtin(name).unwrap_or_else(|| panic!("unknown hint `{}`", name))
+                        }
+                    };
+                    PatternGate::Hint(
+                        hint,
+                        args.iter().map(|a| term(a)).collect(),
+                        out.to_string(),
+                    )
+                }
+                _ => panic!("malformed pattern line `{}`", line),
+            }
+        })
+        .collect()
+}
+
+fn bound_names(pattern: &[PatternGate]) -> HashSet<&str> {
+    let mut names = HashSet::new();
+    for gate in pattern {
+        let (terms, out): (Vec<&Term>, _) = match gate {
+            PatternGate::Add(l, r, o) | PatternGate::Mul(l, r, o) => (vec![l, r], Some(o)),
+            PatternGate::AssertEq(l, r) => (vec![l, r], None),
+            PatternGate::Hint(_, args, o) => (args.iter().collect(), Some(o)),
+        };
+        for term in terms {
+            if let Term::Name(name) = term {
+                names.insert(name.as_str());
+            }
+        }
+        names.extend(out.map(|o| o.as_str()));
+    }
+    names
+}
+
+/// `y = x^5`, the Poseidon and MiMC S-box, in one row instead of three or four
+///
+/// Matches both `x * x * x * x * x` and `x2 = x * x; x4 = x2 * x2; x4 * x`.
+pub fn pow5() -> CustomGate {
+    CustomGate::new(
+        "pow5",
+        5,
+        &[
+            "t2 = x * x\nt3 = t2 * x\nt4 = t3 * x\ny = t4 * x",
+            "t2 = x * x\nt4 = t2 * t2\ny = t4 * x",
+        ],
+        &["x", "_", "y"],
+        |cells| {
+            let (x, y) = (&cells[0], &cells[2]);
+            vec![x
+                .pow(FqElement::BigInt(5.into()))
+                .sub(FqElement::FQ(y.clone()))]
+        },
+    )
+}
+
+/// Affine addition of distinct points `(x1, y1) + (x2, y2) = (x3, y3)` on a short
+/// Weierstrass curve, over three rows instead of sixteen
+///
+/// Matches the chord formula with `l = (y2 - y1) / (x2 - x1)`,
+/// `x3 = l * l - x1 - x2` and `y3 = l * (x1 - x3) - y1` as the DSL lowers it. The slope
+/// keeps its cell and the gate checks exactly what the replaced gates do:
+/// `l·(x2 - x1) = y2 - y1`, `x3 = l² - x1 - x2` and `y3 = l·(x1 - x3) - y1`. For
+/// `x1 = x2` and `y1 = y2` the slope is unconstrained, as it is without the gate, but
+/// `x3` and `y3` still follow from it.
+pub fn ec_add() -> CustomGate {
+    CustomGate::new(
+        "ec_add",
+        2,
+        &["a = y1 * -1
+           dy = y2 + a
+           b = x1 * -1
+           dx = x2 + b
+           l <- div(dy, dx)
+           c = l * dx
+           assert c == dy
+           l2 = l * l
+           d = x1 * -1
+           e = l2 + d
+           f = x2 * -1
+           x3 = e + f
+           g = x3 * -1
+           h = x1 + g
+           i = l * h
+           j = y1 * -1
+           y3 = i + j"],
+        &["x1", "y1", "x2", "y2", "x3", "y3", "l"],
+        |cells| {
+            let [x1, y1, x2, y2, x3, y3, l] = [0, 1, 2, 3, 4, 5, 6].map(|i| cells[i].clone());
+            let sub = |a: &FQ, b: &FQ| a.sub(FqElement::FQ(b.clone()));
+            let add = |a: &FQ, b: &FQ| a.add(FqElement::FQ(b.clone()));
+            let mul = |a: &FQ, b: &FQ| a.mul(FqElement::FQ(b.clone()));
+            vec![
+                sub(&mul(&l, &sub(&x2, &x1)), &sub(&y2, &y1)),
+                sub(&add(&add(&x3, &x1), &x2), &mul(&l, &l)),
+                sub(&add(&y3, &y1), &mul(&l, &sub(&x1, &x3))),
+            ]
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use super::{ec_add, pow5};
+    use crate::plonk::PlonkCircuit;
+    use crate::r1cs::fq;
+    use crate::witness::wire_values;
+    use crate::{flatten, parse};
+
+    fn rows(source: &str) -> (usize, usize) {
+        let circuit = flatten(&parse(source).unwrap()).unwrap();
+        let p = BigInt::from(1009);
+        let plain = PlonkCircuit::from_circuit(&circuit, p.clone()).unwrap();
+        let custom = PlonkCircuit::with_custom_gates(&circuit, p, vec![pow5(), ec_add()]).unwrap();
+        (plain.num_rows(), custom.num_rows())
+    }
+
+    #[test]
+    fn sbox_in_one_row() {
+        let p = BigInt::from(1009);
+        let source = "private x;\noutput y = x * x * x * x * x;";
+        let circuit = flatten(&parse(source).unwrap()).unwrap();
+        let plonk = PlonkCircuit::with_custom_gates(&circuit, p.clone(), vec![pow5()]).unwrap();
+        // the public row for y and the gate
+        assert_eq!(plonk.num_rows(), 2);
+        assert_eq!(plonk.custom, [None, Some(0)]);
+        let wires = wire_values(&circuit, &p, &[], &[3.into()]).unwrap();
+        let mut columns = plonk.assign(&wires);
+        assert!(plonk.is_satisfied(&columns, &[fq(243.into(), &p)]));
+        columns[2][1] = fq(244.into(), &p);
+        assert_eq!(
+            plonk.first_unsatisfied(&columns, &[fq(243.into(), &p)]),
+            Some(1)
+        );
+
+        assert_eq!(
+            rows("private x;\nlet x2 = x * x;\nlet x4 = x2 * x2;\noutput y = x4 * x;"),
+            (4, 2)
+        );
+        // `x2` is needed elsewhere, so the pattern does not apply
+        assert_eq!(
+            rows("private x;\nlet x2 = x * x;\nlet x4 = x2 * x2;\noutput y = x4 * x;\noutput z = x2 + 1;"),
+            (6, 6)
+        );
+    }
+
+    #[test]
+    fn fused_curve_addition() {
+        let source = "private x1, y1, x2, y2;
+let l = (y2 - y1) / (x2 - x1);
+let x3 = l * l - x1 - x2;
+let y3 = l * (x1 - x3) - y1;
+output ox = x3;
+output oy = y3;";
+        let circuit = flatten(&parse(source).unwrap()).unwrap();
+        let p = BigInt::from(1009);
+        let plonk = PlonkCircuit::with_custom_gates(&circuit, p.clone(), vec![ec_add()]).unwrap();
+        // two public rows and the three rows of the gate
+        assert_eq!(plonk.num_rows(), 5);
+        assert_eq!(rows(source).0, 2 + 16);
+
+        let wires = wire_values(&circuit, &p, &[], &[3, 5, 7, 11].map(BigInt::from)).unwrap();
+        let columns = plonk.assign(&wires);
+        let public = [columns[1][3].clone(), columns[2][3].clone()];
+        assert!(plonk.is_satisfied(&columns, &public));
+        let mut wrong = columns.clone();
+        wrong[2][3] = wrong[2][3].add(rust_ecc::field::FqElement::BigInt(1.into()));
+        assert_eq!(plonk.first_unsatisfied(&wrong, &public), Some(2));
+
+        // adding a point to itself leaves the slope free, not the result
+        let gate = ec_add();
+        let cells = |values: [i64; 7]| values.map(|v| fq(v.into(), &p));
+        let zero = fq(0.into(), &p);
+        let (x1, y1) = (3, 5);
+        // l = 2 gives x3 = 4 - 6 and y3 = 2 * (3 - x3) - 5
+        assert!(gate
+            .evaluate(&cells([x1, y1, x1, y1, -2, 5, 2]))
+            .iter()
+            .all(|e| *e == zero));
+        assert!(gate
+            .evaluate(&cells([x1, y1, x1, y1, 100, 200, 2]))
+            .iter()
+            .any(|e| *e != zero));
+    }
+}
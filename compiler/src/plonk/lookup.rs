@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+
+use num_bigint::BigInt;
+use num_traits::{One, Zero};
+use rust_ecc::field::{FqElement, FQ};
+use rust_ecc::math::prime_field_inv;
+
+use crate::r1cs::fq;
+
+/// tables list every entry, `range` and `xor` stop at `2^24` of them
+const MAX_ENTRIES_LOG2: usize = 24;
+
+/// Fixed table of one to three columns that lookup rows are checked against
+///
+/// Circuits declare tables with `table bytes = range(8);` and constrain values to
+/// them with `lookup(bytes, x);`, each lookup becomes one row of a `PlonkCircuit`.
+/// R1CS has no lookup argument and rejects them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LookupTable {
+    pub name: String,
+    /// every entry has the same number of values, one per wire cell it is matched with
+    pub entries: Vec<Vec<BigInt>>,
+}
+
+impl LookupTable {
+    pub fn new(name: &str, entries: Vec<Vec<BigInt>>) -> LookupTable {
+        if let Err(message) = LookupTable::check(&entries) {
+            panic!("{}", message);
+        }
+        LookupTable {
+            name: name.to_string(),
+            entries,
+        }
+    }
+
+    /// `0 <= x < 2^bits`, one column
+    pub fn range(bits: usize) -> LookupTable {
+        assert!(
+            bits <= MAX_ENTRIES_LOG2,
+            "a range table of {} bits has too many entries",
+            bits
+        );
+        let entries = (0..1u64 << bits).map(|x| vec![BigInt::from(x)]).collect();
+        LookupTable::new(&format!("range{}", bits), entries)
+    }
+
+    /// `(a, b, a ^ b)` for all `bits`-bit `a` and `b`
+    pub fn xor(bits: usize) -> LookupTable {
+        assert!(
+            2 * bits <= MAX_ENTRIES_LOG2,
+            "a xor table of {} bits has too many entries",
+            bits
+        );
+        let size = 1u64 << bits;
+        let entries = (0..size)
+            .flat_map(|a| (0..size).map(move |b| [a, b, a ^ b].map(BigInt::from).to_vec()))
+            .collect();
+        LookupTable::new(&format!("xor{}", bits), entries)
+    }
+
+    /// the shape `new` requires, at least one entry and all of one to three values
+    pub fn check(entries: &[Vec<BigInt>]) -> Result<(), &'static str> {
+        let width = entries.first().map_or(1, |e| e.len());
+        if entries.is_empty() || !(1..=3).contains(&width) {
+            return Err("a table needs entries of one to three values");
+        }
+        if entries.iter().any(|e| e.len() != width) {
+            return Err("table entries differ in width");
+        }
+        Ok(())
+    }
+
+    pub fn width(&self) -> usize {
+        self.entries[0].len()
+    }
+
+    /// whether `values` is an entry once both are reduced into the field
+    pub fn contains(&self, values: &[FQ]) -> bool {
+        values.len() == self.width()
+            && self.entries.iter().any(|entry| {
+                entry
+                    .iter()
+                    .zip(values)
+                    .all(|(v, value)| fq(v.clone(), &value.field_modulus) == *value)
+            })
+    }
+
+    /// entries folded into single field elements with `compress`
+    pub fn compressed(&self, alpha: &FQ) -> Vec<FQ> {
+        let p = &alpha.field_modulus;
+        self.entries
+            .iter()
+            .map(|entry| {
+                let values: Vec<FQ> = entry.iter().map(|v| fq(v.clone(), p)).collect();
+                compress(&values, alpha)
+            })
+            .collect()
+    }
+}
+
+/// `v_0 + α·v_1 + α²·v_2`, a tuple lookup becomes a single value lookup
+pub fn compress(values: &[FQ], alpha: &FQ) -> FQ {
+    values
+        .iter()
+        .rev()
+        .fold(fq(BigInt::zero(), &alpha.field_modulus), |acc, v| {
+            acc.mul(FqElement::FQ(alpha.clone()))
+                .add(FqElement::FQ(v.clone()))
+        })
+}
+
+/// Prover messages of the plookup argument for one table
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlookupProof {
+    /// queries and table merged and sorted in table order, `2d + 1` values
+    pub sorted: Vec<FQ>,
+    /// grand product accumulator, starts and ends at one, `d + 1` values
+    pub z: Vec<FQ>,
+}
+
+/// Pad queries `f` and table `t` to the sizes plookup needs, `|f| = d` and `|t| = d + 1`
+///
+/// Padding repeats the first table entry in `f` and the last one in `t`, which keeps
+/// every query in the table and the table order intact.
+fn pad(queries: &[FQ], table: &[FQ]) -> (Vec<FQ>, Vec<FQ>) {
+    let d = (table.len() - 1).max(queries.len());
+    let mut f = queries.to_vec();
+    f.resize(d, table[0].clone());
+    let mut t = table.to_vec();
+    t.resize(d + 1, table[table.len() - 1].clone());
+    (f, t)
+}
+
+/// `γ(1 + β) + a + β·b`
+fn pair(a: &FQ, b: &FQ, beta: &FQ, gamma: &FQ) -> FQ {
+    let one_plus_beta = beta.add(FqElement::BigInt(BigInt::one()));
+    gamma
+        .mul(FqElement::FQ(one_plus_beta))
+        .add(FqElement::FQ(a.clone()))
+        .add(FqElement::FQ(beta.mul(FqElement::FQ(b.clone()))))
+}
+
+/// numerator and denominator of the accumulator step from row `i` to `i + 1`
+fn step(i: usize, f: &[FQ], t: &[FQ], sorted: &[FQ], beta: &FQ, gamma: &FQ) -> (FQ, FQ) {
+    let d = f.len();
+    let (h1, h2) = (&sorted[..=d], &sorted[d..]);
+    let one_plus_beta = beta.add(FqElement::BigInt(BigInt::one()));
+    let numerator = one_plus_beta
+        .mul(FqElement::FQ(gamma.add(FqElement::FQ(f[i].clone()))))
+        .mul(FqElement::FQ(pair(&t[i], &t[i + 1], beta, gamma)));
+    let denominator = pair(&h1[i], &h1[i + 1], beta, gamma).mul(FqElement::FQ(pair(
+        &h2[i],
+        &h2[i + 1],
+        beta,
+        gamma,
+    )));
+    (numerator, denominator)
+}
+
+/// Prove every query appears in the table, following Gabizon and Williamson's plookup
+///
+/// `None` if a query is missing from the table, or in the negligible case that the
+/// challenges hit a zero denominator. The table must not be empty.
+pub fn prove(queries: &[FQ], table: &[FQ], beta: &FQ, gamma: &FQ) -> Option<PlookupProof> {
+    let (f, t) = pad(queries, table);
+    // every query goes right after the first table entry with its value, the table
+    // may contain a value more than once, e.g. after compressing tuples
+    let mut copies: HashMap<&BigInt, usize> = HashMap::new();
+    for v in &f {
+        *copies.entry(&v.n).or_default() += 1;
+    }
+    let mut sorted = Vec::with_capacity(f.len() + t.len());
+    for v in &t {
+        sorted.push(v.clone());
+        for _ in 0..copies.remove(&v.n).unwrap_or(0) {
+            sorted.push(v.clone());
+        }
+    }
+    if !copies.is_empty() {
+        return None;
+    }
+
+    let p = &beta.field_modulus;
+    let mut z = vec![fq(BigInt::one(), p)];
+    for i in 0..f.len() {
+        let (numerator, denominator) = step(i, &f, &t, &sorted, beta, gamma);
+        if denominator.n.is_zero() {
+            return None;
+        }
+        let inverse = fq(prime_field_inv(denominator.n, p.clone()), p);
+        let next = z[i]
+            .mul(FqElement::FQ(numerator))
+            .mul(FqElement::FQ(inverse));
+        z.push(next);
+    }
+    Some(PlookupProof { sorted, z })
+}
+
+/// Check the accumulator: `z_0 = z_d = 1` and
+/// `z_{i+1}·(γ(1+β) + h1_i + β·h1_{i+1})·(γ(1+β) + h2_i + β·h2_{i+1})
+///  = z_i·(1+β)·(γ + f_i)·(γ(1+β) + t_i + β·t_{i+1})`
+/// with `h1` and `h2` the two halves of the sorted vector sharing their middle value
+pub fn verify(queries: &[FQ], table: &[FQ], proof: &PlookupProof, beta: &FQ, gamma: &FQ) -> bool {
+    let (f, t) = pad(queries, table);
+    let d = f.len();
+    if proof.sorted.len() != 2 * d + 1 || proof.z.len() != d + 1 {
+        return false;
+    }
+    let one = |v: &FQ| v.n.is_one();
+    one(&proof.z[0])
+        && one(&proof.z[d])
+        && (0..d).all(|i| {
+            let (numerator, denominator) = step(i, &f, &t, &proof.sorted, beta, gamma);
+            proof.z[i + 1].mul(FqElement::FQ(denominator))
+                == proof.z[i].mul(FqElement::FQ(numerator))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use super::{prove, verify, LookupTable};
+    use crate::r1cs::fq;
+    use crate::Field;
+
+    #[test]
+    fn plookup_accepts_members_only() {
+        let p = Field::Bn128.modulus();
+        let (alpha, beta, gamma) = (fq(3.into(), &p), fq(1234.into(), &p), fq(98765.into(), &p));
+        let table = LookupTable::range(4).compressed(&alpha);
+        let queries: Vec<_> = [3, 3, 15, 0, 7]
+            .iter()
+            .map(|v| fq(BigInt::from(*v), &p))
+            .collect();
+        let proof = prove(&queries, &table, &beta, &gamma).unwrap();
+        assert!(verify(&queries, &table, &proof, &beta, &gamma));
+
+        // more queries than table entries
+        let many: Vec<_> = (0..40).map(|v| fq(BigInt::from(v % 16), &p)).collect();
+        let proof = prove(&many, &table, &beta, &gamma).unwrap();
+        assert!(verify(&many, &table, &proof, &beta, &gamma));
+
+        let outside = [fq(16.into(), &p)];
+        assert!(prove(&outside, &table, &beta, &gamma).is_none());
+        // reusing an honest sorted vector for a query outside the table
+        let mut forged = prove(&[fq(15.into(), &p)], &table, &beta, &gamma).unwrap();
+        assert!(!verify(&outside, &table, &forged, &beta, &gamma));
+        let last = forged.sorted.len() - 1;
+        forged.sorted.swap(0, last);
+        assert!(!verify(
+            &[fq(15.into(), &p)],
+            &table,
+            &forged,
+            &beta,
+            &gamma
+        ));
+    }
+
+    #[test]
+    fn xor_table_rows() {
+        let table = LookupTable::xor(2);
+        assert_eq!(table.entries.len(), 16);
+        assert_eq!(table.width(), 3);
+        assert!(table
+            .entries
+            .contains(&[3, 1, 2].map(BigInt::from).to_vec()));
+    }
+
+    #[test]
+    #[should_panic(expected = "a range table of 64 bits has too many entries")]
+    fn oversized_range_table() {
+        LookupTable::range(64);
+    }
+}
@@ -0,0 +1,247 @@
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_traits::{One, Zero};
+use rust_ecc::field::{FqElement, FQ};
+use rust_ecc::ntt::{self, NttField};
+use rust_ecc::polynomial::Polynomial;
+
+use crate::field::Field;
+use crate::r1cs::{fq, LinearCombination, R1CS};
+
+/// Quadratic Arithmetic Program derived from an R1CS
+///
+/// Constraint `i` is mapped to the evaluation point `domain_i`, every variable gets
+/// polynomials `A_j`, `B_j`, `C_j` taking its matrix column values at those points.
+/// A witness `w` satisfies the R1CS iff `Z(x)` divides
+/// `(Σ w_j A_j(x)) * (Σ w_j B_j(x)) - Σ w_j C_j(x)`.
+///
+/// When the field has enough roots of unity the domain is the subgroup `⟨ω⟩` of the
+/// next power of two above the constraint count, rows past the last constraint are
+/// `0 * 0 = 0`. Interpolation and the quotient then take O(n log n) with the NTT and
+/// `Z(x) = x^n - 1`. Fields without a large enough subgroup, like the secp256k1 order
+/// beyond 32 constraints, fall back to the points `1..=m` and quadratic Lagrange
+/// interpolation and division.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QAP {
+    pub field_modulus: BigInt,
+    pub domain: Vec<FQ>,
+    pub a: Vec<Polynomial>,
+    pub b: Vec<Polynomial>,
+    pub c: Vec<Polynomial>,
+    /// target polynomial `Z(x) = Π (x - domain_i)`
+    pub z: Polynomial,
+    /// roots of unity the domain is taken from, `None` for the points `1..=m`
+    pub ntt: Option<NttField>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QapError {
+    /// the field is too small to give every constraint its own evaluation point
+    DomainTooLarge { constraints: usize },
+}
+
+impl fmt::Display for QapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QapError::DomainTooLarge { constraints } => write!(
+                f,
+                "the field has fewer than {} distinct evaluation points",
+                constraints
+            ),
+        }
+    }
+}
+
+impl std::error::Error for QapError {}
+
+impl QAP {
+    pub fn from_r1cs(r1cs: &R1CS) -> Result<QAP, QapError> {
+        let p = &r1cs.field_modulus;
+        let n = r1cs.num_constraints().next_power_of_two();
+        // the product A * B has degree up to 2n - 2 and is computed on 2n points
+        let ntt = match Field::from_modulus(p) {
+            Some(field) => field.ntt(),
+            None => NttField::from_modulus(p.clone()),
+        }
+        .filter(|field| 2 * n <= 1 << field.two_adicity.min(usize::BITS - 1));
+        let domain: Vec<FQ> = match &ntt {
+            Some(field) => field.domain(n),
+            None => {
+                // points past p - 1 wrap around onto zero and the first points
+                if BigInt::from(r1cs.num_constraints()) >= *p {
+                    return Err(QapError::DomainTooLarge {
+                        constraints: r1cs.num_constraints(),
+                    });
+                }
+                (1..=r1cs.num_constraints())
+                    .map(|i| fq(BigInt::from(i), p))
+                    .collect()
+            }
+        };
+        let columns = |rows: &[LinearCombination]| -> Vec<Polynomial> {
+            let zero = fq(BigInt::zero(), p);
+            let mut columns = vec![vec![zero; domain.len()]; r1cs.num_variables];
+            for (i, row) in rows.iter().enumerate() {
+                for (v, coeff) in &row.terms {
+                    columns[*v][i] = coeff.clone();
+                }
+            }
+            columns
+                .into_iter()
+                .map(|values| match &ntt {
+                    Some(field) => Polynomial::new(ntt::intt(&values, field), p.clone()),
+                    None => {
+                        let points: Vec<(FQ, FQ)> = domain.iter().cloned().zip(values).collect();
+                        Polynomial::interpolate(&points, p.clone())
+                    }
+                })
+                .collect()
+        };
+        let z = match &ntt {
+            Some(_) => {
+                let mut coeffs = vec![BigInt::zero(); n + 1];
+                coeffs[0] = BigInt::from(-1);
+                coeffs[n] = BigInt::one();
+                Polynomial::from_ints(&coeffs, p.clone())
+            }
+            None => domain.iter().fold(
+                Polynomial::from_ints(&[BigInt::one()], p.clone()),
+                |z, x| z.mul(&Polynomial::linear(x)),
+            ),
+        };
+        Ok(QAP {
+            field_modulus: p.clone(),
+            a: columns(&r1cs.a),
+            b: columns(&r1cs.b),
+            c: columns(&r1cs.c),
+            domain,
+            z,
+            ntt,
+        })
+    }
+
+    /// `(A(x), B(x), C(x))` for a full witness
+    pub fn combine(&self, witness: &[FQ]) -> (Polynomial, Polynomial, Polynomial) {
+        let combine = |polys: &[Polynomial]| {
+            polys.iter().zip(witness).fold(
+                Polynomial::zero(self.field_modulus.clone()),
+                |acc, (poly, w)| acc.add(&poly.scale(w)),
+            )
+        };
+        (combine(&self.a), combine(&self.b), combine(&self.c))
+    }
+
+    /// `A(x) * B(x) - C(x)` for a full witness
+    pub fn target(&self, witness: &[FQ]) -> Polynomial {
+        let (a, b, c) = self.combine(witness);
+        let product = match &self.ntt {
+            Some(field) => ntt::multiply(&a, &b, field),
+            None => a.mul(&b),
+        };
+        product.sub(&c)
+    }
+
+    /// Quotient `H(x) = (A(x) * B(x) - C(x)) / Z(x)`, `None` if the witness does not
+    /// satisfy the constraints and the division leaves a remainder
+    pub fn quotient(&self, witness: &[FQ]) -> Option<Polynomial> {
+        let target = self.target(witness);
+        let (h, remainder) = match &self.ntt {
+            Some(_) => divide_by_vanishing(&target, self.domain.len()),
+            None => target.div_rem(&self.z),
+        };
+        remainder.is_zero().then_some(h)
+    }
+}
+
+/// `t(x) / (x^n - 1)` and the remainder in a single pass over the coefficients
+fn divide_by_vanishing(t: &Polynomial, n: usize) -> (Polynomial, Polynomial) {
+    let p = &t.field_modulus;
+    let mut remainder = t.coeffs.clone();
+    let mut quotient = vec![fq(BigInt::zero(), p); remainder.len().saturating_sub(n)];
+    // c·x^i = c·x^(i-n)·(x^n - 1) + c·x^(i-n), highest degree first
+    for i in (n..remainder.len()).rev() {
+        let c = std::mem::replace(&mut remainder[i], fq(BigInt::zero(), p));
+        remainder[i - n] = remainder[i - n].add(FqElement::FQ(c.clone()));
+        quotient[i - n] = c;
+    }
+    (
+        Polynomial::new(quotient, p.clone()),
+        Polynomial::new(remainder, p.clone()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+    use num_traits::Zero;
+
+    use super::{QapError, QAP};
+    use crate::test_utils::witness;
+    use crate::{flatten, parse, Field, R1CS};
+
+    fn cubic(p: &BigInt) -> (R1CS, QAP) {
+        let program =
+            parse("public out;\nprivate x;\nlet y = x * x * x + x + 5;\nassert_eq(y, out);")
+                .unwrap();
+        let r1cs = R1CS::from_circuit(&flatten(&program).unwrap(), p.clone()).unwrap();
+        let qap = QAP::from_r1cs(&r1cs).unwrap();
+        (r1cs, qap)
+    }
+
+    #[test]
+    fn columns_interpolate_matrix_entries() {
+        // 96 = 2^5 * 3 has room for a domain of 8, 100 = 2^2 * 25 does not
+        for (p, domain) in [(97, 8), (101, 5)] {
+            let p = BigInt::from(p);
+            let (r1cs, qap) = cubic(&p);
+            assert_eq!(qap.domain.len(), domain);
+            assert_eq!(qap.ntt.is_some(), domain == 8);
+            for (i, x) in qap.domain.iter().enumerate() {
+                for v in 0..r1cs.num_variables {
+                    // rows past the last constraint are all zero
+                    let expected = r1cs
+                        .a
+                        .get(i)
+                        .and_then(|row| row.terms.iter().find(|(var, _)| *var == v))
+                        .map(|(_, c)| c.n.clone())
+                        .unwrap_or_else(BigInt::zero);
+                    assert_eq!(qap.a[v].evaluate(x).n, expected);
+                }
+                assert!(qap.z.evaluate(x).n.is_zero());
+            }
+        }
+    }
+
+    #[test]
+    fn quotient_exists_only_for_valid_witness() {
+        for p in [BigInt::from(97), BigInt::from(101), Field::Bn128.modulus()] {
+            let (r1cs, qap) = cubic(&p);
+            // [1, out, x, x^2, x^3, x^3 + x, y]
+            let valid = witness(&[1, 35, 3, 9, 27, 30, 35], &p);
+            assert!(r1cs.is_satisfied(&valid));
+            let h = qap.quotient(&valid).unwrap();
+            assert_eq!(h.mul(&qap.z), qap.target(&valid));
+            // deg H <= deg(A * B - C) - deg Z = 2 * (n - 1) - n
+            assert!(h.degree() <= Some(qap.domain.len() - 2));
+            let invalid = witness(&[1, 36, 3, 9, 27, 30, 35], &p);
+            assert!(qap.quotient(&invalid).is_none());
+        }
+
+        // the secp256k1 order has roots of unity for up to 32 constraints
+        let (_, qap) = cubic(&Field::Secp256k1Order.modulus());
+        assert_eq!(qap.domain.len(), 8);
+        assert!(qap.ntt.is_some());
+        // a composite modulus falls back instead of searching for a non-residue forever
+        let (_, qap) = cubic(&BigInt::from(1225));
+        assert!(qap.ntt.is_none());
+
+        // x = 4 and x = 1 are the same point in F_3
+        let program = parse("private x;\noutput y = x * x * x * x * x * x;").unwrap();
+        let r1cs = R1CS::from_circuit(&flatten(&program).unwrap(), BigInt::from(3)).unwrap();
+        assert_eq!(
+            QAP::from_r1cs(&r1cs),
+            Err(QapError::DomainTooLarge { constraints: 5 })
+        );
+    }
+}
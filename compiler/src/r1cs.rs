@@ -0,0 +1,390 @@
+use num_bigint::BigInt;
+use num_traits::{One, Zero};
+use rust_ecc::field::{FqElement, FQ};
+
+use crate::ast::Visibility;
+use crate::circuit::{Circuit, GateKind, Operand, Wire, WireKind};
+use crate::error::CompileError;
+
+/// reduce an integer into the field
+pub(crate) fn fq(n: BigInt, field_modulus: &BigInt) -> FQ {
+    FQ::default().new(FqElement::BigInt(n), field_modulus.clone())
+}
+
+/// reject a modulus below two before reducing anything by it
+pub(crate) fn check_modulus(field_modulus: &BigInt) -> Result<(), CompileError> {
+    if *field_modulus < BigInt::from(2) {
+        return Err(CompileError::InvalidModulus {
+            field_modulus: field_modulus.clone(),
+        });
+    }
+    Ok(())
+}
+
+/// Sparse row of an R1CS matrix, a list of (variable index, coefficient)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LinearCombination {
+    pub terms: Vec<(usize, FQ)>,
+}
+
+impl LinearCombination {
+    /// add `coeff * variable`, merging with an existing term for the same variable
+    pub fn add_term(&mut self, variable: usize, coeff: FQ) {
+        match self.terms.iter_mut().find(|(v, _)| *v == variable) {
+            Some((_, existing)) => *existing = existing.add(FqElement::FQ(coeff)),
+            None => self.terms.push((variable, coeff)),
+        }
+        self.terms.retain(|(_, c)| !c.n.is_zero());
+    }
+
+    pub fn add(&self, other: &LinearCombination) -> LinearCombination {
+        let mut out = self.clone();
+        for (v, coeff) in &other.terms {
+            out.add_term(*v, coeff.clone());
+        }
+        out
+    }
+
+    pub fn sub(&self, other: &LinearCombination) -> LinearCombination {
+        let mut out = self.clone();
+        for (v, coeff) in &other.terms {
+            out.add_term(*v, coeff.mul(FqElement::BigInt(-BigInt::one())));
+        }
+        out
+    }
+
+    pub fn scale(&self, factor: &FQ) -> LinearCombination {
+        let mut out = LinearCombination::default();
+        for (v, coeff) in &self.terms {
+            out.add_term(*v, coeff.mul(FqElement::FQ(factor.clone())));
+        }
+        out
+    }
+
+    pub fn evaluate(&self, witness: &[FQ], field_modulus: &BigInt) -> FQ {
+        self.terms
+            .iter()
+            .fold(fq(BigInt::zero(), field_modulus), |acc, (v, coeff)| {
+                acc.add(FqElement::FQ(coeff.mul(FqElement::FQ(witness[*v].clone()))))
+            })
+    }
+}
+
+/// Rank-1 constraint system `A·w ∘ B·w = C·w` over a prime field
+///
+/// The variable vector is laid out as
+/// `[1, outputs..., public inputs..., private inputs..., intermediate wires...]`,
+/// outputs and public inputs together form the public part.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct R1CS {
+    pub field_modulus: BigInt,
+    pub num_outputs: usize,
+    pub num_public: usize,
+    pub num_private: usize,
+    pub num_variables: usize,
+    pub a: Vec<LinearCombination>,
+    pub b: Vec<LinearCombination>,
+    pub c: Vec<LinearCombination>,
+    /// variable index of every circuit wire, `None` for wires merged into linear combinations
+    pub wire_variables: Vec<Option<usize>>,
+    /// index of the gate each constraint was lowered from, `None` for constraints added by hand
+    pub constraint_gates: Vec<Option<usize>>,
+}
+
+impl R1CS {
+    /// index of the constant-one variable
+    pub const ONE: usize = 0;
+
+    /// Lower a flattened gate list over a `Field` or any other prime, every addition,
+    /// multiplication and assertion becomes one constraint
+    ///
+    /// Fails on a modulus below two, a constant that does not fit in the field and on
+    /// lookups, R1CS has no lookup argument.
+    pub fn from_circuit(
+        circuit: &Circuit,
+        field_modulus: impl Into<BigInt>,
+    ) -> Result<R1CS, CompileError> {
+        let field_modulus = field_modulus.into();
+        check_modulus(&field_modulus)?;
+        circuit.check_constants(&field_modulus)?;
+        Lowering::new(circuit, field_modulus, false).run()
+    }
+
+    /// Lower a flattened gate list, additions and multiplications by a constant are
+    /// folded into linear combinations and only cost a constraint when they produce an output
+    pub fn from_circuit_merged(
+        circuit: &Circuit,
+        field_modulus: impl Into<BigInt>,
+    ) -> Result<R1CS, CompileError> {
+        let field_modulus = field_modulus.into();
+        check_modulus(&field_modulus)?;
+        circuit.check_constants(&field_modulus)?;
+        Lowering::new(circuit, field_modulus, true).run()
+    }
+
+    pub fn num_constraints(&self) -> usize {
+        self.a.len()
+    }
+
+    pub fn push(&mut self, a: LinearCombination, b: LinearCombination, c: LinearCombination) {
+        self.a.push(a);
+        self.b.push(b);
+        self.c.push(c);
+        self.constraint_gates.push(None);
+    }
+
+    /// range of variable indices holding outputs
+    pub fn output_variables(&self) -> std::ops::Range<usize> {
+        1..1 + self.num_outputs
+    }
+
+    /// range of variable indices holding outputs and public inputs
+    pub fn public_variables(&self) -> std::ops::Range<usize> {
+        1..1 + self.num_outputs + self.num_public
+    }
+
+    /// range of variable indices holding private inputs
+    pub fn private_variables(&self) -> std::ops::Range<usize> {
+        let start = self.public_variables().end;
+        start..start + self.num_private
+    }
+
+    /// name of every variable, taken from the circuit wire it holds, `v<index>` for
+    /// variables added by hand
+    pub fn variable_names(&self, circuit: &Circuit) -> Vec<String> {
+        let mut names: Vec<String> = (0..self.num_variables).map(|v| format!("v{}", v)).collect();
+        names[Self::ONE] = "one".to_string();
+        for (wire, variable) in self.wire_variables.iter().enumerate() {
+            if let Some(variable) = variable {
+                names[*variable] = circuit.wire_name(Wire(wire));
+            }
+        }
+        names
+    }
+
+    /// check `A·w * B·w == C·w` for a single constraint
+    pub fn is_constraint_satisfied(&self, index: usize, witness: &[FQ]) -> bool {
+        let a = self.a[index].evaluate(witness, &self.field_modulus);
+        let b = self.b[index].evaluate(witness, &self.field_modulus);
+        let c = self.c[index].evaluate(witness, &self.field_modulus);
+        a.mul(FqElement::FQ(b)) == c
+    }
+
+    /// a full witness assigns every variable, starting with the constant one
+    pub fn is_satisfied(&self, witness: &[FQ]) -> bool {
+        witness.len() == self.num_variables
+            && witness[Self::ONE].n.is_one()
+            && self.first_unsatisfied(witness).is_none()
+    }
+
+    /// index of the first constraint the witness violates
+    pub fn first_unsatisfied(&self, witness: &[FQ]) -> Option<usize> {
+        (0..self.num_constraints()).find(|i| !self.is_constraint_satisfied(*i, witness))
+    }
+}
+
+struct Lowering<'a> {
+    circuit: &'a Circuit,
+    r1cs: R1CS,
+    /// linear combination every computed wire evaluates to
+    wire_lcs: Vec<Option<LinearCombination>>,
+    merge_linear: bool,
+}
+
+impl<'a> Lowering<'a> {
+    fn new(circuit: &'a Circuit, field_modulus: BigInt, merge_linear: bool) -> Lowering<'a> {
+        let outputs = circuit.outputs();
+        let public = circuit.inputs(Visibility::Public);
+        let private = circuit.inputs(Visibility::Private);
+        let mut lowering = Lowering {
+            circuit,
+            r1cs: R1CS {
+                field_modulus,
+                num_outputs: outputs.len(),
+                num_public: public.len(),
+                num_private: private.len(),
+                num_variables: 1,
+                a: Vec::new(),
+                b: Vec::new(),
+                c: Vec::new(),
+                wire_variables: vec![None; circuit.wires.len()],
+                constraint_gates: Vec::new(),
+            },
+            wire_lcs: vec![None; circuit.wires.len()],
+            merge_linear,
+        };
+        for wire in outputs.iter().chain(&public).chain(&private) {
+            lowering.allocate(*wire);
+        }
+        lowering
+    }
+
+    fn run(mut self) -> Result<R1CS, CompileError> {
+        for (index, gate) in self.circuit.gates.iter().enumerate() {
+            let constraints = self.r1cs.num_constraints();
+            match &gate.kind {
+                GateKind::Add { lhs, rhs, out } => {
+                    let mut sum = self.operand_lc(lhs);
+                    for (v, coeff) in self.operand_lc(rhs).terms {
+                        sum.add_term(v, coeff);
+                    }
+                    self.linear(sum, *out);
+                }
+                GateKind::Mul { lhs, rhs, out } => {
+                    let (lhs, rhs) = (self.operand_lc(lhs), self.operand_lc(rhs));
+                    match (self.constant_of(&lhs), self.constant_of(&rhs)) {
+                        (Some(k), _) if self.merge_linear => self.linear(rhs.scale(&k), *out),
+                        (_, Some(k)) if self.merge_linear => self.linear(lhs.scale(&k), *out),
+                        _ => {
+                            let out = self.allocate(*out);
+                            self.r1cs.push(lhs, rhs, out);
+                        }
+                    }
+                }
+                GateKind::AssertEq { lhs, rhs } => {
+                    let (lhs, rhs) = (self.operand_lc(lhs), self.operand_lc(rhs));
+                    let one = self.constant_lc(BigInt::one());
+                    self.r1cs.push(lhs, one, rhs);
+                }
+                // only needs a variable, the gates checking it add the constraints
+                GateKind::Hint { out, .. } => {
+                    self.allocate(*out);
+                }
+                GateKind::Lookup { .. } => {
+                    return Err(CompileError::Unsupported {
+                        message: "R1CS has no lookup argument, lookups need the PLONK backend",
+                        span: gate.span,
+                    })
+                }
+            }
+            for origin in &mut self.r1cs.constraint_gates[constraints..] {
+                *origin = Some(index);
+            }
+        }
+        Ok(self.r1cs)
+    }
+
+    /// `lc * 1 = out`, skipped when merging and `out` does not need its own variable
+    fn linear(&mut self, lc: LinearCombination, out: Wire) {
+        if self.merge_linear && self.circuit.wires[out.0].kind == WireKind::Internal {
+            self.wire_lcs[out.0] = Some(lc);
+            return;
+        }
+        let one = self.constant_lc(BigInt::one());
+        let out = self.allocate(out);
+        self.r1cs.push(lc, one, out);
+    }
+
+    /// give a wire its own variable, returns the variable as a linear combination
+    fn allocate(&mut self, wire: Wire) -> LinearCombination {
+        let variable = match self.r1cs.wire_variables[wire.0] {
+            Some(variable) => variable,
+            None => {
+                let variable = self.r1cs.num_variables;
+                self.r1cs.num_variables += 1;
+                self.r1cs.wire_variables[wire.0] = Some(variable);
+                variable
+            }
+        };
+        let mut lc = LinearCombination::default();
+        lc.add_term(variable, fq(BigInt::one(), &self.r1cs.field_modulus));
+        self.wire_lcs[wire.0] = Some(lc.clone());
+        lc
+    }
+
+    /// value of a linear combination that only uses the constant-one variable
+    fn constant_of(&self, lc: &LinearCombination) -> Option<FQ> {
+        match lc.terms.as_slice() {
+            [] => Some(fq(BigInt::zero(), &self.r1cs.field_modulus)),
+            [(R1CS::ONE, k)] => Some(k.clone()),
+            _ => None,
+        }
+    }
+
+    fn constant_lc(&self, n: BigInt) -> LinearCombination {
+        let mut lc = LinearCombination::default();
+        lc.add_term(R1CS::ONE, fq(n, &self.r1cs.field_modulus));
+        lc
+    }
+
+    fn operand_lc(&self, operand: &Operand) -> LinearCombination {
+        match operand {
+            Operand::Constant(n) => self.constant_lc(n.clone()),
+            Operand::Wire(wire) => self.wire_lcs[wire.0]
+                .clone()
+                .expect("gates are ordered so that inputs are computed first"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use super::R1CS;
+    use crate::test_utils::witness;
+    use crate::{evaluate, flatten, parse, CompileError, EvalError, R1CSBuilder, WitnessBuilder};
+
+    #[test]
+    fn multiplication_program() {
+        // a * b = c with a public product
+        let p = BigInt::from(101);
+        let program = parse("public c;\nprivate a, b;\nassert_eq(a * b, c);").unwrap();
+        let r1cs = R1CS::from_circuit(&flatten(&program).unwrap(), p.clone()).unwrap();
+        assert_eq!(r1cs.num_constraints(), 2);
+        assert_eq!(r1cs.num_variables, 5);
+        assert_eq!(r1cs.public_variables(), 1..2);
+        assert_eq!(r1cs.private_variables(), 2..4);
+        // [1, c, a, b, a*b]
+        assert!(r1cs.is_satisfied(&witness(&[1, 6, 2, 3, 6], &p)));
+        assert!(!r1cs.is_satisfied(&witness(&[1, 7, 2, 3, 6], &p)));
+    }
+
+    #[test]
+    fn constants_and_subtraction_wrap_around() {
+        let p = BigInt::from(101);
+        let program = parse("private x;\nlet y = 3 - x;\nassert_eq(y + x, 3);").unwrap();
+        let r1cs = R1CS::from_circuit(&flatten(&program).unwrap(), p.clone()).unwrap();
+        // [1, x, -x, y, y + x]
+        assert!(r1cs.is_satisfied(&witness(&[1, 5, -5, -2, 3], &p)));
+        assert!(!r1cs.is_satisfied(&witness(&[1, 5, -5, -1, 4], &p)));
+    }
+
+    #[test]
+    fn outputs_come_first() {
+        let p = BigInt::from(101);
+        let program = parse("public a;\nprivate b;\noutput c = a * b;").unwrap();
+        let r1cs = R1CS::from_circuit(&flatten(&program).unwrap(), p.clone()).unwrap();
+        assert_eq!(r1cs.output_variables(), 1..2);
+        assert_eq!(r1cs.public_variables(), 1..3);
+        assert_eq!(r1cs.private_variables(), 3..4);
+        // [1, c, a, b]
+        assert!(r1cs.is_satisfied(&witness(&[1, 12, 3, 4], &p)));
+    }
+
+    #[test]
+    fn rejects_modulus_below_two() {
+        let program = parse("private a;\noutput b = a * 3;").unwrap();
+        let circuit = flatten(&program).unwrap();
+        for p in [1, 0, -7] {
+            let p = BigInt::from(p);
+            let invalid = CompileError::InvalidModulus {
+                field_modulus: p.clone(),
+            };
+            assert_eq!(
+                R1CS::from_circuit(&circuit, p.clone()),
+                Err(invalid.clone())
+            );
+            assert_eq!(
+                R1CS::from_circuit_merged(&circuit, p.clone()),
+                Err(invalid.clone())
+            );
+            assert_eq!(
+                evaluate(&program, &[], &[1.into()], p.clone()),
+                Err(EvalError::Compile(invalid.clone()))
+            );
+            assert_eq!(R1CSBuilder::new(p.clone()).err(), Some(invalid.clone()));
+            assert_eq!(WitnessBuilder::new(p).err(), Some(invalid));
+        }
+    }
+}
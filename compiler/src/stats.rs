@@ -0,0 +1,204 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fmt::Write;
+
+use crate::ast::Visibility;
+use crate::circuit::{Circuit, GateKind, Operand, Wire, WireKind};
+use crate::r1cs::R1CS;
+
+/// Size of a compiled circuit, see `stats`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CircuitStats {
+    pub constraints: usize,
+    pub variables: usize,
+    pub wires: usize,
+    pub public_inputs: usize,
+    pub private_inputs: usize,
+    pub outputs: usize,
+    pub additions: usize,
+    pub multiplications: usize,
+    pub assertions: usize,
+    pub hints: usize,
+    /// most distinct variables a single constraint reads, grows when linear
+    /// combinations are merged
+    pub max_fan_in: usize,
+}
+
+impl fmt::Display for CircuitStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "constraints: {}, variables: {}, max fan-in: {}",
+            self.constraints, self.variables, self.max_fan_in
+        )?;
+        writeln!(
+            f,
+            "wires: {}, public inputs: {}, private inputs: {}, outputs: {}",
+            self.wires, self.public_inputs, self.private_inputs, self.outputs
+        )?;
+        writeln!(
+            f,
+            "gates: {} additions, {} multiplications, {} assertions, {} hints",
+            self.additions, self.multiplications, self.assertions, self.hints
+        )
+    }
+}
+
+/// Count the gates of a circuit and the constraints it was lowered to
+pub fn stats(circuit: &Circuit, r1cs: &R1CS) -> CircuitStats {
+    let count = |f: fn(&GateKind) -> bool| circuit.gates.iter().filter(|g| f(&g.kind)).count();
+    let max_fan_in = (0..r1cs.num_constraints())
+        .map(|i| {
+            [&r1cs.a[i], &r1cs.b[i], &r1cs.c[i]]
+                .iter()
+                .flat_map(|lc| lc.terms.iter().map(|(v, _)| *v))
+                .filter(|v| *v != R1CS::ONE)
+                .collect::<HashSet<usize>>()
+                .len()
+        })
+        .max()
+        .unwrap_or(0);
+    CircuitStats {
+        constraints: r1cs.num_constraints(),
+        variables: r1cs.num_variables,
+        wires: circuit.wires.len(),
+        public_inputs: circuit.inputs(Visibility::Public).len(),
+        private_inputs: circuit.inputs(Visibility::Private).len(),
+        outputs: circuit.outputs().len(),
+        additions: count(|k| matches!(k, GateKind::Add { .. })),
+        multiplications: count(|k| matches!(k, GateKind::Mul { .. })),
+        assertions: count(|k| matches!(k, GateKind::AssertEq { .. })),
+        hints: count(|k| matches!(k, GateKind::Hint { .. })),
+        max_fan_in,
+    }
+}
+
+/// Graphviz rendering of the gate graph, e.g. `dot -Tsvg circuit.dot`
+///
+/// Inputs and outputs are boxes, public ones filled, gates are circles labelled
+/// with their operation. Intermediate wires are drawn as edges between gates,
+/// labelled with their source name when they have one.
+pub fn to_dot(circuit: &Circuit) -> String {
+    let mut producers: HashMap<Wire, usize> = HashMap::new();
+    for (index, gate) in circuit.gates.iter().enumerate() {
+        if let GateKind::Add { out, .. } | GateKind::Mul { out, .. } | GateKind::Hint { out, .. } =
+            &gate.kind
+        {
+            producers.insert(*out, index);
+        }
+    }
+    // internal wires with a producing gate are edges, every other wire is a node
+    let is_edge = |wire: Wire| {
+        circuit.wires[wire.0].kind == WireKind::Internal && producers.contains_key(&wire)
+    };
+
+    let mut out = String::from("digraph circuit {\n  rankdir=LR;\n");
+    for (index, info) in circuit.wires.iter().enumerate() {
+        let wire = Wire(index);
+        if is_edge(wire) {
+            continue;
+        }
+        let style = match info.kind {
+            WireKind::Input(Visibility::Public) | WireKind::Output => ", style=filled",
+            _ => "",
+        };
+        let name = quote(&circuit.wire_name(wire));
+        writeln!(out, "  w{} [label={}, shape=box{}];", index, name, style).unwrap();
+    }
+    for (index, gate) in circuit.gates.iter().enumerate() {
+        let (label, args, result) = match &gate.kind {
+            GateKind::Add { lhs, rhs, out } => ("+", vec![lhs.clone(), rhs.clone()], Some(out)),
+            GateKind::Mul { lhs, rhs, out } => ("*", vec![lhs.clone(), rhs.clone()], Some(out)),
+            GateKind::AssertEq { lhs, rhs } => ("==", vec![lhs.clone(), rhs.clone()], None),
+            GateKind::Hint { hint, args, out } => (hint.name(), args.clone(), Some(out)),
+            GateKind::Lookup { table, args } => (
+                circuit.tables[*table].name.as_str(),
+                args.iter().map(|wire| Operand::Wire(*wire)).collect(),
+                None,
+            ),
+        };
+        writeln!(out, "  g{} [label={}, shape=circle];", index, quote(label)).unwrap();
+        for (position, arg) in args.into_iter().enumerate() {
+            match arg {
+                Operand::Constant(n) => {
+                    writeln!(
+                        out,
+                        "  c{}_{} [label={}, shape=plaintext];",
+                        index,
+                        position,
+                        quote(&n.to_string())
+                    )
+                    .unwrap();
+                    writeln!(out, "  c{}_{} -> g{};", index, position, index).unwrap();
+                }
+                Operand::Wire(wire) if is_edge(wire) => {
+                    let label = match &circuit.wires[wire.0].name {
+                        Some(name) => format!(" [label={}]", quote(name)),
+                        None => String::new(),
+                    };
+                    writeln!(out, "  g{} -> g{}{};", producers[&wire], index, label).unwrap();
+                }
+                Operand::Wire(wire) => writeln!(out, "  w{} -> g{};", wire.0, index).unwrap(),
+            }
+        }
+        if let Some(result) = result.filter(|w| !is_edge(**w)) {
+            writeln!(out, "  g{} -> w{};", index, result.0).unwrap();
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use super::{stats, to_dot};
+    use crate::{flatten, parse, R1CS};
+
+    #[test]
+    fn counts_gates_and_fan_in() {
+        let source = "public y;\nprivate a, b, c;\nlet s = a + b + c;\nassert_eq(s * a, y);\noutput o = inv(b);";
+        let circuit = flatten(&parse(source).unwrap()).unwrap();
+        let r1cs = R1CS::from_circuit_merged(&circuit, BigInt::from(1009)).unwrap();
+        let report = stats(&circuit, &r1cs);
+        assert_eq!(
+            (report.additions, report.multiplications, report.hints),
+            (2, 2, 1)
+        );
+        assert_eq!((report.public_inputs, report.private_inputs), (1, 3));
+        // (a + b + c) * a = w
+        assert_eq!(report.max_fan_in, 4);
+        assert!(report
+            .to_string()
+            .starts_with(&format!("constraints: {}, ", r1cs.num_constraints())));
+    }
+
+    #[test]
+    fn dot_graph() {
+        let circuit =
+            flatten(&parse("private a, b;\nlet p = a * b;\noutput c = p + 5;").unwrap()).unwrap();
+        assert_eq!(
+            to_dot(&circuit),
+            "digraph circuit {
+  rankdir=LR;
+  w0 [label=\"a\", shape=box];
+  w1 [label=\"b\", shape=box];
+  w3 [label=\"c\", shape=box, style=filled];
+  g0 [label=\"*\", shape=circle];
+  w0 -> g0;
+  w1 -> g0;
+  g1 [label=\"+\", shape=circle];
+  g0 -> g1 [label=\"p\"];
+  c1_1 [label=\"5\", shape=plaintext];
+  c1_1 -> g1;
+  g1 -> w3;
+}
+"
+        );
+    }
+}
@@ -0,0 +1,16 @@
+// helpers shared by the unit tests
+
+use num_bigint::BigInt;
+use rust_ecc::field::FQ;
+
+use crate::r1cs::fq;
+
+/// circuit inputs from small integers
+pub fn ints(values: &[i64]) -> Vec<BigInt> {
+    values.iter().map(|v| BigInt::from(*v)).collect()
+}
+
+/// a witness vector from small integers, `values[0]` is the constant one
+pub fn witness(values: &[i64], p: &BigInt) -> Vec<FQ> {
+    values.iter().map(|v| fq(BigInt::from(*v), p)).collect()
+}
@@ -0,0 +1,207 @@
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_traits::{One, Zero};
+use rust_ecc::field::{FqElement, FQ};
+
+use crate::ast::{Span, Visibility};
+use crate::circuit::{Circuit, GateKind, Hint, Operand};
+use crate::r1cs::{fq, R1CS};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WitnessError {
+    /// the number of provided inputs does not match the declarations
+    InputCount {
+        visibility: Visibility,
+        expected: usize,
+        found: usize,
+    },
+    /// a hint has no answer for its arguments, e.g. a division by zero
+    HintFailed { hint: Hint, gate: usize, span: Span },
+    /// the inputs are inconsistent, `gate` and `span` point at the failing assertion
+    Unsatisfied {
+        constraint: usize,
+        gate: Option<usize>,
+        span: Option<Span>,
+    },
+}
+
+impl fmt::Display for WitnessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WitnessError::InputCount {
+                visibility,
+                expected,
+                found,
+            } => write!(
+                f,
+                "expected {} {} input(s), found {}",
+                expected,
+                visibility.keyword(),
+                found
+            ),
+            WitnessError::HintFailed { hint, gate, .. } => write!(
+                f,
+                "`{}` of gate {} has no value for its arguments",
+                hint.name(),
+                gate
+            ),
+            WitnessError::Unsatisfied {
+                constraint,
+                gate: Some(gate),
+                ..
+            } => write!(
+                f,
+                "constraint {} of gate {} does not hold",
+                constraint, gate
+            ),
+            WitnessError::Unsatisfied { constraint, .. } => {
+                write!(f, "constraint {} does not hold", constraint)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WitnessError {}
+
+/// Compute the full R1CS variable assignment for a circuit
+///
+/// `public` and `private` are given in declaration order, every gate output is computed
+/// from its inputs and the result is checked against all constraints.
+pub fn generate_witness(
+    circuit: &Circuit,
+    r1cs: &R1CS,
+    public: &[BigInt],
+    private: &[BigInt],
+) -> Result<Vec<FQ>, WitnessError> {
+    let p = &r1cs.field_modulus;
+    let wires = wire_values(circuit, p, public, private)?;
+    let mut witness = vec![fq(BigInt::zero(), p); r1cs.num_variables];
+    witness[R1CS::ONE] = fq(BigInt::one(), p);
+    for (wire, variable) in r1cs.wire_variables.iter().enumerate() {
+        if let (Some(variable), Some(value)) = (variable, &wires[wire]) {
+            witness[*variable] = value.clone();
+        }
+    }
+
+    match r1cs.first_unsatisfied(&witness) {
+        Some(constraint) => {
+            let gate = r1cs.constraint_gates[constraint];
+            Err(WitnessError::Unsatisfied {
+                constraint,
+                gate,
+                span: gate.map(|g| circuit.gates[g].span),
+            })
+        }
+        None => Ok(witness),
+    }
+}
+
+/// Value of every wire, independent of how the circuit is lowered
+///
+/// Wires no gate computes, e.g. ones the optimizer folded away, are `None`.
+/// Assertions and lookups are not checked here.
+pub fn wire_values(
+    circuit: &Circuit,
+    field_modulus: &BigInt,
+    public: &[BigInt],
+    private: &[BigInt],
+) -> Result<Vec<Option<FQ>>, WitnessError> {
+    let p = field_modulus;
+    let mut wires: Vec<Option<FQ>> = vec![None; circuit.wires.len()];
+    for (visibility, values) in [(Visibility::Public, public), (Visibility::Private, private)] {
+        let inputs = circuit.inputs(visibility);
+        if inputs.len() != values.len() {
+            return Err(WitnessError::InputCount {
+                visibility,
+                expected: inputs.len(),
+                found: values.len(),
+            });
+        }
+        for (wire, value) in inputs.iter().zip(values) {
+            wires[wire.0] = Some(fq(value.clone(), p));
+        }
+    }
+
+    let value = |wires: &[Option<FQ>], operand: &Operand| match operand {
+        Operand::Constant(n) => fq(n.clone(), p),
+        Operand::Wire(wire) => wires[wire.0]
+            .clone()
+            .expect("gates are ordered so that inputs are computed first"),
+    };
+    for (index, gate) in circuit.gates.iter().enumerate() {
+        match &gate.kind {
+            GateKind::Add { lhs, rhs, out } => {
+                let sum = value(&wires, lhs).add(FqElement::FQ(value(&wires, rhs)));
+                wires[out.0] = Some(sum);
+            }
+            GateKind::Mul { lhs, rhs, out } => {
+                let product = value(&wires, lhs).mul(FqElement::FQ(value(&wires, rhs)));
+                wires[out.0] = Some(product);
+            }
+            GateKind::AssertEq { .. } | GateKind::Lookup { .. } => {}
+            GateKind::Hint { hint, args, out } => {
+                let args: Vec<FQ> = args.iter().map(|a| value(&wires, a)).collect();
+                let result = hint.compute(&args, p).ok_or(WitnessError::HintFailed {
+                    hint: *hint,
+                    gate: index,
+                    span: gate.span,
+                })?;
+                wires[out.0] = Some(result);
+            }
+        }
+    }
+    Ok(wires)
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use super::{generate_witness, WitnessError};
+    use crate::ast::Visibility;
+    use crate::test_utils::ints;
+    use crate::{flatten, parse, R1CS};
+
+    #[test]
+    fn computes_intermediate_wires() {
+        let source = "public out;\nprivate x;\nlet y = x * x * x + x + 5;\nassert_eq(y, out);";
+        let circuit = flatten(&parse(source).unwrap()).unwrap();
+        let r1cs = R1CS::from_circuit(&circuit, BigInt::from(101)).unwrap();
+        let witness = generate_witness(&circuit, &r1cs, &ints(&[35]), &ints(&[3])).unwrap();
+        let values: Vec<BigInt> = witness.iter().map(|v| v.n.clone()).collect();
+        assert_eq!(values, ints(&[1, 35, 3, 9, 27, 30, 35]));
+        assert!(r1cs.is_satisfied(&witness));
+    }
+
+    #[test]
+    fn reports_failing_assertion() {
+        let source = "public c;\nprivate a, b;\nassert_eq(a * b, c);";
+        let circuit = flatten(&parse(source).unwrap()).unwrap();
+        let r1cs = R1CS::from_circuit(&circuit, BigInt::from(101)).unwrap();
+        let err = generate_witness(&circuit, &r1cs, &ints(&[7]), &ints(&[2, 3])).unwrap_err();
+        assert_eq!(err.to_string(), "constraint 1 of gate 1 does not hold");
+        let WitnessError::Unsatisfied {
+            constraint,
+            gate,
+            span,
+        } = err
+        else {
+            panic!("expected an unsatisfied constraint, found {:?}", err);
+        };
+        assert_eq!((constraint, gate), (1, Some(1)));
+        let span = span.unwrap();
+        assert_eq!(&source[span.start..span.end], "assert_eq(a * b, c);");
+
+        let err = generate_witness(&circuit, &r1cs, &ints(&[6]), &ints(&[2])).unwrap_err();
+        assert_eq!(
+            err,
+            WitnessError::InputCount {
+                visibility: Visibility::Private,
+                expected: 2,
+                found: 1
+            }
+        );
+        assert_eq!(err.to_string(), "expected 2 private input(s), found 1");
+    }
+}
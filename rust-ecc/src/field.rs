@@ -1,7 +1,7 @@
 use crate::math::{modulo, prime_field_inv};
 use num_bigint::BigInt;
 
-#[derive(Default)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct FQ {
     pub n: BigInt,
     pub field_modulus: BigInt,
@@ -14,7 +14,7 @@ impl FQ {
         match val {
             FqElement::FQ(val) => FQ {
                 n: val.n,
-                field_modulus: field_modulus,
+                field_modulus,
             },
             FqElement::BigInt(n) => FQ {
                 n: modulo(&n, &field_modulus),
@@ -42,6 +42,25 @@ impl FQ {
         }
     }
 
+    pub fn sub(&self, other: FqElement) -> FQ {
+        match other {
+            FqElement::FQ(other) => {
+                let out = modulo(&(&self.n - other.n), &self.field_modulus);
+                FQ {
+                    n: out,
+                    field_modulus: self.field_modulus.clone(),
+                }
+            }
+            FqElement::BigInt(n) => {
+                let out = modulo(&(&self.n - n), &self.field_modulus);
+                FQ {
+                    n: out,
+                    field_modulus: self.field_modulus.clone(),
+                }
+            }
+        }
+    }
+
     pub fn mul(&self, other: FqElement) -> FQ {
         match other {
             FqElement::FQ(other) => {
@@ -105,6 +124,7 @@ impl FQ {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn eq(&self, other: FqElement) -> bool {
         match other {
             FqElement::FQ(other) => self.n == other.n,
@@ -113,7 +133,8 @@ impl FQ {
     }
 }
 
-enum FqElement {
+#[derive(Clone, Debug)]
+pub enum FqElement {
     FQ(FQ),
     BigInt(BigInt),
 }
@@ -128,6 +149,7 @@ pub struct FQP {
 }
 
 impl FQP {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(&mut self, coeffs: Vec<FqElement>, modulus_coeffs: Vec<FqElement>) {
         if coeffs.len() != modulus_coeffs.len() {
             panic!("coeffs and modulus_coeffs aren't of the same length");
@@ -1,3 +1,5 @@
 pub mod curve;
 pub mod field;
 pub mod math;
+pub mod ntt;
+pub mod polynomial;
@@ -28,3 +28,97 @@ pub fn prime_field_inv(mut a: BigInt, n: BigInt) -> BigInt {
     }
     modulo(&lm, &n)
 }
+
+pub fn prime_field_sqrt(a: BigInt, p: BigInt) -> Option<BigInt> {
+    // Tonelli-Shanks, returns the smaller of the two roots or None for a non-residue
+    // p has to be prime, for a composite p this gives up with None instead of looping
+    let two = BigInt::from(2);
+    if p < two {
+        return None;
+    }
+    let a = modulo(&a, &p);
+    if a.is_zero() || p == two {
+        // 0 and 1 are their own roots
+        return Some(a);
+    }
+    let legendre = |x: &BigInt| x.modpow(&((&p - 1) / &two), &p);
+    if legendre(&a) != BigInt::one() {
+        return None;
+    }
+    // p - 1 = q * 2^s with q odd
+    let (mut q, mut s): (BigInt, u32) = (&p - 1, 0);
+    while (&q % &two).is_zero() {
+        q /= &two;
+        s += 1;
+    }
+    // any quadratic non-residue
+    let mut z = two.clone();
+    while legendre(&z) == BigInt::one() {
+        z += 1;
+        if z >= p {
+            return None;
+        }
+    }
+    let mut m = s;
+    let mut c = z.modpow(&q, &p);
+    let mut t = a.modpow(&q, &p);
+    let mut r = a.modpow(&((&q + 1) / &two), &p);
+    while !t.is_one() {
+        // smallest i with t^(2^i) = 1
+        let (mut i, mut t2i) = (0u32, t.clone());
+        while !t2i.is_one() {
+            t2i = modulo(&(&t2i * &t2i), &p);
+            i += 1;
+            if i >= m {
+                return None;
+            }
+        }
+        let b = c.modpow(&(BigInt::one() << (m - i - 1)), &p);
+        m = i;
+        c = modulo(&(&b * &b), &p);
+        t = modulo(&(&t * &c), &p);
+        r = modulo(&(&r * &b), &p);
+    }
+    if modulo(&(&r * &r), &p) != a {
+        return None;
+    }
+    let other = &p - &r;
+    Some(r.min(other))
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+
+    use super::{modulo, prime_field_sqrt};
+
+    #[test]
+    fn square_roots() {
+        // 1009 - 1 = 63 * 2^4 exercises the Tonelli-Shanks loop
+        let p = BigInt::from(1009);
+        let mut residues = 0;
+        for a in 0..1009 {
+            let a = BigInt::from(a);
+            if let Some(r) = prime_field_sqrt(a.clone(), p.clone()) {
+                assert_eq!(modulo(&(&r * &r), &p), a);
+                assert!(r <= &p - &r);
+                residues += 1;
+            }
+        }
+        // zero and the (p - 1) / 2 non-zero squares
+        assert_eq!(residues, 505);
+        assert_eq!(prime_field_sqrt(BigInt::from(-1), BigInt::from(7)), None);
+
+        // moduli without a non-residue search that ends
+        assert_eq!(prime_field_sqrt(BigInt::from(1), BigInt::from(2)), Some(1.into()));
+        assert_eq!(prime_field_sqrt(BigInt::from(4), BigInt::from(1)), None);
+        for p in [9, 15, 21, 25] {
+            for a in 0..p {
+                let p = BigInt::from(p);
+                if let Some(r) = prime_field_sqrt(BigInt::from(a), p.clone()) {
+                    assert_eq!(modulo(&(&r * &r), &p), BigInt::from(a));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use crate::field::{FqElement, FQ};
+use crate::polynomial::Polynomial;
+use num_bigint::BigInt;
+use num_traits::{Num, One, Zero};
+
+/// Prime field whose multiplicative group has a large power-of-two subgroup,
+/// `p - 1 = 2^two_adicity * odd`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NttField {
+    pub modulus: BigInt,
+    pub two_adicity: u32,
+    /// generator of the full multiplicative group, also used as the default coset shift
+    pub generator: BigInt,
+}
+
+/// Scalar field of the bn128 (alt_bn128 / BN254) curve
+pub fn bn128_scalar() -> NttField {
+    NttField {
+        modulus: BigInt::from_str_radix(
+            "21888242871839275222246405745257275088548364400416034343698204186575808495617",
+            10,
+        )
+        .expect("Failed to construct BigInt from str"),
+        two_adicity: 28,
+        generator: BigInt::from(5u8),
+    }
+}
+
+/// Goldilocks field `2^64 - 2^32 + 1`
+pub fn goldilocks() -> NttField {
+    NttField {
+        modulus: BigInt::from(18446744069414584321u64),
+        two_adicity: 32,
+        generator: BigInt::from(7u8),
+    }
+}
+
+impl NttField {
+    /// Any odd prime, `p - 1` decides the two-adicity
+    ///
+    /// The smallest quadratic non-residue stands in for the group generator. It has
+    /// the full two-adic order, which is all `root_of_unity` needs, but as a coset
+    /// shift it is only safe for domains smaller than `2^two_adicity`.
+    ///
+    /// `None` for a modulus of 2 or less, or when no non-residue turns up among the
+    /// first `2·log2(p)^2` candidates. A prime always has one there (assuming the
+    /// generalized Riemann hypothesis), this bounds the search for composites.
+    pub fn from_modulus(modulus: BigInt) -> Option<NttField> {
+        if modulus <= BigInt::from(2u8) {
+            return None;
+        }
+        let p_minus_one = &modulus - BigInt::one();
+        let two_adicity = p_minus_one.trailing_zeros().unwrap() as u32;
+        let half = &p_minus_one >> 1;
+        let bits = modulus.bits();
+        let limit = BigInt::from(2 * bits * bits).min(p_minus_one.clone());
+        let mut generator = BigInt::from(2u8);
+        while generator.modpow(&half, &modulus) != p_minus_one {
+            generator += 1;
+            if generator > limit {
+                return None;
+            }
+        }
+        Some(NttField {
+            modulus,
+            two_adicity,
+            generator,
+        })
+    }
+
+    fn element(&self, n: BigInt) -> FQ {
+        FQ::default().new(FqElement::BigInt(n), self.modulus.clone())
+    }
+
+    /// primitive `n`-th root of unity, `n` must be a power of two dividing `2^two_adicity`
+    pub fn root_of_unity(&self, n: usize) -> FQ {
+        assert!(n.is_power_of_two(), "domain size must be a power of two");
+        let log_n = n.trailing_zeros();
+        assert!(
+            log_n <= self.two_adicity,
+            "domain of size 2^{} exceeds the two-adicity of the field",
+            log_n
+        );
+        let exponent = (&self.modulus - BigInt::one()) >> log_n;
+        self.element(self.generator.modpow(&exponent, &self.modulus))
+    }
+
+    /// `[1, ω, ω^2, ..., ω^(n-1)]`
+    pub fn domain(&self, n: usize) -> Vec<FQ> {
+        let omega = self.root_of_unity(n);
+        let mut points = vec![self.element(BigInt::one())];
+        for i in 1..n {
+            points.push(points[i - 1].mul(FqElement::FQ(omega.clone())));
+        }
+        points
+    }
+}
+
+/// Evaluate a polynomial given by `coeffs` on the `n`-th roots of unity, `n = coeffs.len()`
+pub fn ntt(coeffs: &[FQ], field: &NttField) -> Vec<FQ> {
+    let omega = field.root_of_unity(coeffs.len());
+    transform(coeffs, &omega)
+}
+
+/// Interpolate coefficients from evaluations on the `n`-th roots of unity
+pub fn intt(evals: &[FQ], field: &NttField) -> Vec<FQ> {
+    let omega_inv = field
+        .element(BigInt::one())
+        .div(FqElement::FQ(field.root_of_unity(evals.len())));
+    let n_inv = field
+        .element(BigInt::one())
+        .div(FqElement::BigInt(BigInt::from(evals.len())));
+    transform(evals, &omega_inv)
+        .into_iter()
+        .map(|v| v.mul(FqElement::FQ(n_inv.clone())))
+        .collect()
+}
+
+/// Evaluate on the coset `shift·⟨ω⟩`, used to divide by the vanishing polynomial of
+/// the subgroup without hitting its roots
+pub fn coset_ntt(coeffs: &[FQ], shift: &FQ, field: &NttField) -> Vec<FQ> {
+    let mut power = field.element(BigInt::one());
+    let shifted: Vec<FQ> = coeffs
+        .iter()
+        .map(|c| {
+            let out = c.mul(FqElement::FQ(power.clone()));
+            power = power.mul(FqElement::FQ(shift.clone()));
+            out
+        })
+        .collect();
+    ntt(&shifted, field)
+}
+
+/// Inverse of `coset_ntt`
+pub fn coset_intt(evals: &[FQ], shift: &FQ, field: &NttField) -> Vec<FQ> {
+    let shift_inv = field
+        .element(BigInt::one())
+        .div(FqElement::FQ(shift.clone()));
+    let mut power = field.element(BigInt::one());
+    intt(evals, field)
+        .into_iter()
+        .map(|c| {
+            let out = c.mul(FqElement::FQ(power.clone()));
+            power = power.mul(FqElement::FQ(shift_inv.clone()));
+            out
+        })
+        .collect()
+}
+
+/// Multiply two polynomials in O(n log n) by pointwise multiplication in evaluation form
+pub fn multiply(lhs: &Polynomial, rhs: &Polynomial, field: &NttField) -> Polynomial {
+    if lhs.is_zero() || rhs.is_zero() {
+        return Polynomial::zero(field.modulus.clone());
+    }
+    let n = (lhs.coeffs.len() + rhs.coeffs.len() - 1).next_power_of_two();
+    let pad = |coeffs: &[FQ]| {
+        let mut padded = coeffs.to_vec();
+        padded.resize(n, field.element(BigInt::zero()));
+        ntt(&padded, field)
+    };
+    let evals: Vec<FQ> = pad(&lhs.coeffs)
+        .into_iter()
+        .zip(pad(&rhs.coeffs))
+        .map(|(l, r)| l.mul(FqElement::FQ(r)))
+        .collect();
+    Polynomial::new(intt(&evals, field), field.modulus.clone())
+}
+
+// iterative radix-2 Cooley-Tukey on a bit-reversed copy of the input
+fn transform(values: &[FQ], omega: &FQ) -> Vec<FQ> {
+    let n = values.len();
+    assert!(n.is_power_of_two(), "input length must be a power of two");
+    let log_n = n.trailing_zeros();
+    let mut out: Vec<FQ> = (0..n)
+        .map(|i| values[bit_reverse(i, log_n)].clone())
+        .collect();
+    let mut len = 2;
+    while len <= n {
+        // ω_len = ω^(n / len) is a primitive len-th root of unity
+        let step = omega.pow(FqElement::BigInt(BigInt::from(n / len)));
+        for start in (0..n).step_by(len) {
+            let mut twiddle = FQ::default().new(
+                FqElement::BigInt(BigInt::one()),
+                omega.field_modulus.clone(),
+            );
+            for i in 0..len / 2 {
+                let even = out[start + i].clone();
+                let odd = out[start + i + len / 2].mul(FqElement::FQ(twiddle.clone()));
+                out[start + i] = even.add(FqElement::FQ(odd.clone()));
+                out[start + i + len / 2] = even.sub(FqElement::FQ(odd));
+                twiddle = twiddle.mul(FqElement::FQ(step.clone()));
+            }
+        }
+        len *= 2;
+    }
+    out
+}
+
+fn bit_reverse(i: usize, bits: u32) -> usize {
+    if bits == 0 {
+        return 0;
+    }
+    i.reverse_bits() >> (usize::BITS - bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bn128_scalar, coset_intt, coset_ntt, goldilocks, intt, multiply, ntt, NttField};
+    use crate::field::FqElement;
+    use crate::polynomial::Polynomial;
+    use num_bigint::BigInt;
+    use num_traits::One;
+
+    fn poly(coeffs: &[i64], modulus: &BigInt) -> Polynomial {
+        let coeffs: Vec<BigInt> = coeffs.iter().map(|c| BigInt::from(*c)).collect();
+        Polynomial::from_ints(&coeffs, modulus.clone())
+    }
+
+    #[test]
+    fn roots_of_unity_have_exact_order() {
+        let bn128 = NttField::from_modulus(bn128_scalar().modulus).unwrap();
+        assert_eq!(bn128.two_adicity, 28);
+        // no x has x^((p - 1) / 2) = -1 for these composites, the search used to hang
+        for composite in [9, 25, 49] {
+            assert_eq!(NttField::from_modulus(BigInt::from(composite)), None);
+        }
+        assert_eq!(NttField::from_modulus(BigInt::from(2)), None);
+        for field in [bn128_scalar(), goldilocks(), bn128] {
+            let n = 1usize << 20;
+            let omega = field.root_of_unity(n);
+            assert!(omega.pow(FqElement::BigInt(BigInt::from(n))).n.is_one());
+            assert!(!omega.pow(FqElement::BigInt(BigInt::from(n / 2))).n.is_one());
+        }
+    }
+
+    #[test]
+    fn ntt_matches_naive_evaluation() {
+        let field = goldilocks();
+        let f = poly(&[3, -1, 4, 1, -5, 9, 2, 6], &field.modulus);
+        let evals = ntt(&f.coeffs, &field);
+        for (x, y) in field.domain(8).iter().zip(&evals) {
+            assert_eq!(&f.evaluate(x), y);
+        }
+        assert_eq!(intt(&evals, &field), f.coeffs);
+
+        let shift = field.element(field.generator.clone());
+        let coset = coset_ntt(&f.coeffs, &shift, &field);
+        for (x, y) in field.domain(8).iter().zip(&coset) {
+            assert_eq!(&f.evaluate(&x.mul(FqElement::FQ(shift.clone()))), y);
+        }
+        assert_eq!(coset_intt(&coset, &shift, &field), f.coeffs);
+    }
+
+    #[test]
+    fn multiply_matches_schoolbook() {
+        let field = bn128_scalar();
+        let a = poly(&[1, 2, 3, 4, 5], &field.modulus);
+        let b = poly(&[-7, 0, 11], &field.modulus);
+        assert_eq!(multiply(&a, &b, &field), a.mul(&b));
+    }
+}
@@ -0,0 +1,243 @@
+use crate::field::{FqElement, FQ};
+use num_bigint::BigInt;
+use num_traits::{One, Zero};
+
+/// Dense univariate polynomial over a prime field,
+/// coefficients are stored lowest degree first without trailing zeros
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Polynomial {
+    pub coeffs: Vec<FQ>,
+    pub field_modulus: BigInt,
+}
+
+fn element(n: BigInt, field_modulus: &BigInt) -> FQ {
+    FQ::default().new(FqElement::BigInt(n), field_modulus.clone())
+}
+
+impl Polynomial {
+    pub fn new(coeffs: Vec<FQ>, field_modulus: BigInt) -> Polynomial {
+        let mut poly = Polynomial {
+            coeffs,
+            field_modulus,
+        };
+        poly.trim();
+        poly
+    }
+
+    /// build from integer coefficients, lowest degree first
+    pub fn from_ints(coeffs: &[BigInt], field_modulus: BigInt) -> Polynomial {
+        let coeffs = coeffs
+            .iter()
+            .map(|c| element(c.clone(), &field_modulus))
+            .collect();
+        Polynomial::new(coeffs, field_modulus)
+    }
+
+    pub fn zero(field_modulus: BigInt) -> Polynomial {
+        Polynomial {
+            coeffs: Vec::new(),
+            field_modulus,
+        }
+    }
+
+    /// the polynomial `x - root`
+    pub fn linear(root: &FQ) -> Polynomial {
+        let field_modulus = root.field_modulus.clone();
+        let coeffs = vec![
+            element(-root.n.clone(), &field_modulus),
+            element(BigInt::one(), &field_modulus),
+        ];
+        Polynomial::new(coeffs, field_modulus)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.coeffs.is_empty()
+    }
+
+    /// degree of the polynomial, `None` for the zero polynomial
+    pub fn degree(&self) -> Option<usize> {
+        self.coeffs.len().checked_sub(1)
+    }
+
+    fn trim(&mut self) {
+        while self.coeffs.last().is_some_and(|c| c.n.is_zero()) {
+            self.coeffs.pop();
+        }
+    }
+
+    fn coeff(&self, i: usize) -> FQ {
+        self.coeffs
+            .get(i)
+            .cloned()
+            .unwrap_or_else(|| element(BigInt::zero(), &self.field_modulus))
+    }
+
+    pub fn add(&self, other: &Polynomial) -> Polynomial {
+        let len = self.coeffs.len().max(other.coeffs.len());
+        let coeffs = (0..len)
+            .map(|i| self.coeff(i).add(FqElement::FQ(other.coeff(i))))
+            .collect();
+        Polynomial::new(coeffs, self.field_modulus.clone())
+    }
+
+    pub fn sub(&self, other: &Polynomial) -> Polynomial {
+        let len = self.coeffs.len().max(other.coeffs.len());
+        let coeffs = (0..len)
+            .map(|i| self.coeff(i).sub(FqElement::FQ(other.coeff(i))))
+            .collect();
+        Polynomial::new(coeffs, self.field_modulus.clone())
+    }
+
+    pub fn mul(&self, other: &Polynomial) -> Polynomial {
+        if self.is_zero() || other.is_zero() {
+            return Polynomial::zero(self.field_modulus.clone());
+        }
+        let mut coeffs = vec![
+            element(BigInt::zero(), &self.field_modulus);
+            self.coeffs.len() + other.coeffs.len() - 1
+        ];
+        for (i, a) in self.coeffs.iter().enumerate() {
+            for (j, b) in other.coeffs.iter().enumerate() {
+                coeffs[i + j] = coeffs[i + j].add(FqElement::FQ(a.mul(FqElement::FQ(b.clone()))));
+            }
+        }
+        Polynomial::new(coeffs, self.field_modulus.clone())
+    }
+
+    /// multiply every coefficient by a field element
+    pub fn scale(&self, factor: &FQ) -> Polynomial {
+        let coeffs = self
+            .coeffs
+            .iter()
+            .map(|c| c.mul(FqElement::FQ(factor.clone())))
+            .collect();
+        Polynomial::new(coeffs, self.field_modulus.clone())
+    }
+
+    /// long division, returns (quotient, remainder)
+    pub fn div_rem(&self, divisor: &Polynomial) -> (Polynomial, Polynomial) {
+        let lead = divisor
+            .coeffs
+            .last()
+            .expect("division by the zero polynomial");
+        let mut remainder = self.coeffs.clone();
+        if remainder.len() < divisor.coeffs.len() {
+            return (Polynomial::zero(self.field_modulus.clone()), self.clone());
+        }
+        let mut quotient = vec![
+            element(BigInt::zero(), &self.field_modulus);
+            remainder.len() - divisor.coeffs.len() + 1
+        ];
+        for i in (0..quotient.len()).rev() {
+            let factor = remainder[i + divisor.coeffs.len() - 1].div(FqElement::FQ(lead.clone()));
+            for (j, d) in divisor.coeffs.iter().enumerate() {
+                remainder[i + j] =
+                    remainder[i + j].sub(FqElement::FQ(factor.mul(FqElement::FQ(d.clone()))));
+            }
+            quotient[i] = factor;
+        }
+        remainder.truncate(divisor.coeffs.len() - 1);
+        (
+            Polynomial::new(quotient, self.field_modulus.clone()),
+            Polynomial::new(remainder, self.field_modulus.clone()),
+        )
+    }
+
+    /// evaluate at `x` using Horner's rule
+    pub fn evaluate(&self, x: &FQ) -> FQ {
+        self.coeffs
+            .iter()
+            .rev()
+            .fold(element(BigInt::zero(), &self.field_modulus), |acc, c| {
+                acc.mul(FqElement::FQ(x.clone()))
+                    .add(FqElement::FQ(c.clone()))
+            })
+    }
+
+    pub fn derivative(&self) -> Polynomial {
+        let coeffs = self
+            .coeffs
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(i, c)| c.mul(FqElement::BigInt(BigInt::from(i))))
+            .collect();
+        Polynomial::new(coeffs, self.field_modulus.clone())
+    }
+
+    /// Lagrange interpolation of the unique polynomial of degree < n through n points
+    /// with distinct x coordinates
+    pub fn interpolate(points: &[(FQ, FQ)], field_modulus: BigInt) -> Polynomial {
+        let mut out = Polynomial::zero(field_modulus.clone());
+        for (i, (xi, yi)) in points.iter().enumerate() {
+            if yi.n.is_zero() {
+                continue;
+            }
+            // basis polynomial l_i(x) = Π_{j != i} (x - x_j) / (x_i - x_j)
+            let mut basis = Polynomial::from_ints(&[BigInt::one()], field_modulus.clone());
+            let mut denominator = element(BigInt::one(), &field_modulus);
+            for (j, (xj, _)) in points.iter().enumerate() {
+                if i != j {
+                    basis = basis.mul(&Polynomial::linear(xj));
+                    denominator = denominator.mul(FqElement::FQ(xi.sub(FqElement::FQ(xj.clone()))));
+                }
+            }
+            out = out.add(&basis.scale(&yi.div(FqElement::FQ(denominator))));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{element, Polynomial};
+    use num_bigint::BigInt;
+
+    fn poly(coeffs: &[i64]) -> Polynomial {
+        let coeffs: Vec<BigInt> = coeffs.iter().map(|c| BigInt::from(*c)).collect();
+        Polynomial::from_ints(&coeffs, BigInt::from(97))
+    }
+
+    #[test]
+    fn arithmetic_and_division() {
+        // (x + 1)(x - 1) = x^2 - 1
+        let product = poly(&[1, 1]).mul(&poly(&[-1, 1]));
+        assert_eq!(product, poly(&[-1, 0, 1]));
+        assert_eq!(product.add(&poly(&[1])), poly(&[0, 0, 1]));
+        assert_eq!(product.sub(&product), poly(&[]));
+        assert_eq!(product.degree(), Some(2));
+
+        let (q, r) = poly(&[4, 0, 3, 1]).div_rem(&poly(&[1, 1]));
+        assert_eq!(q.mul(&poly(&[1, 1])).add(&r), poly(&[4, 0, 3, 1]));
+        assert!(r.degree().unwrap_or(0) < 1);
+        let (q, r) = product.div_rem(&poly(&[1, 1]));
+        assert_eq!(q, poly(&[-1, 1]));
+        assert!(r.is_zero());
+    }
+
+    #[test]
+    fn evaluate_and_derivative() {
+        let p = BigInt::from(97);
+        // 3x^3 + 2x + 5
+        let f = poly(&[5, 2, 0, 3]);
+        assert_eq!(
+            f.evaluate(&element(BigInt::from(2), &p)).n,
+            BigInt::from(33)
+        );
+        assert_eq!(f.derivative(), poly(&[2, 0, 9]));
+        assert!(poly(&[7]).derivative().is_zero());
+    }
+
+    #[test]
+    fn interpolate_through_points() {
+        let p = BigInt::from(97);
+        let f = poly(&[5, 2, 0, 3]);
+        let points: Vec<_> = (10..14)
+            .map(|x| {
+                let x = element(BigInt::from(x), &p);
+                (x.clone(), f.evaluate(&x))
+            })
+            .collect();
+        assert_eq!(Polynomial::interpolate(&points, p), f);
+    }
+}